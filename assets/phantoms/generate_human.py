@@ -0,0 +1,128 @@
+"""Generates human.phantom, a stylised adult in the spirit of the MIRD phantom.
+
+The body is built from ellipsoids and elliptic cylinders on a 1 cm grid, the
+organs are tagged with their ICRP 103 tissue. All tissues are water with the
+MIRD densities, run `python3 generate_human.py` in this directory after changes.
+"""
+
+import os
+from collections import Counter
+
+NX, NY, NZ = 48, 176, 30
+
+# MIRD densities in kg/m3
+SOFT_TISSUE = 1040
+LUNG = 296
+SKELETON = 1486
+
+organs = [
+    (0, "-", "Außen", None, None),
+    (1, "Water", "Weichgewebe", "remainder", SOFT_TISSUE),
+    (2, "Water", "Haut", "skin", SOFT_TISSUE),
+    (3, "Water", "Lunge", "lung", LUNG),
+    (4, "Water", "Magen", "stomach", SOFT_TISSUE),
+    (5, "Water", "Dickdarm", "colon", SOFT_TISSUE),
+    (6, "Water", "Leber", "liver", SOFT_TISSUE),
+    (7, "Water", "Blase", "bladder", SOFT_TISSUE),
+    (8, "Water", "Speiseröhre", "oesophagus", SOFT_TISSUE),
+    (9, "Water", "Schilddrüse", "thyroid", SOFT_TISSUE),
+    (10, "Water", "Gehirn", "brain", SOFT_TISSUE),
+    (11, "Water", "Speicheldrüsen", "salivary_glands", SOFT_TISSUE),
+    (12, "Water", "Brust", "breast", SOFT_TISSUE),
+    (13, "Water", "Keimdrüsen", "gonads", SOFT_TISSUE),
+    (14, "Water", "Rotes Knochenmark", "red_marrow", SKELETON),
+    (15, "Water", "Knochenoberfläche", "bone_surface", SKELETON),
+]
+
+def ell(p, c, r):
+    return sum(((p[i] - c[i]) / r[i]) ** 2 for i in range(3)) <= 1.0
+
+def cyl_y(p, cx, cz, rx, rz, y0, y1):
+    return y0 <= p[1] < y1 and ((p[0] - cx) / rx) ** 2 + ((p[2] - cz) / rz) ** 2 <= 1.0
+
+def body(p):
+    x, y, z = p
+    return (
+        ell(p, (0, 163, 0), (8, 11, 10))
+        or cyl_y(p, 0, 0, 5, 5, 145, 155)
+        or cyl_y(p, 0, 0, 17, 10, 76, 145)
+        or cyl_y(p, -9, 0, 7, 7, 0, 78)
+        or cyl_y(p, 9, 0, 7, 7, 0, 78)
+        or cyl_y(p, -20.5, 0, 3, 3, 85, 145)
+        or cyl_y(p, 20.5, 0, 3, 3, 85, 145)
+        or ell(p, (-8, 128, -10), (4, 4, 4))
+        or ell(p, (8, 128, -10), (4, 4, 4))
+    )
+
+def organ(p):
+    x, y, z = p
+    if ell(p, (-8, 128, -10), (4, 4, 4)) or ell(p, (8, 128, -10), (4, 4, 4)):
+        if z < -8:
+            return 12
+    if ell(p, (0, 166, 0), (6, 7, 8)):
+        return 10
+    if ell(p, (0, 163, 0), (8, 11, 10)) and not ell(p, (0, 166, 0), (7, 8, 9)) and y > 160:
+        return 15
+    if ell(p, (-4, 156, -1), (1.5, 1.5, 1.5)) or ell(p, (4, 156, -1), (1.5, 1.5, 1.5)):
+        return 11
+    if ell(p, (0, 148, -3), (2, 2, 1.5)):
+        return 9
+    if cyl_y(p, 0, 7, 1.5, 1.5, 80, 145) or ell(p, (0, 84, 5), (9, 4, 3)):
+        return 14
+    if cyl_y(p, 0, 7, 2.5, 2.5, 80, 145):
+        return 15
+    if cyl_y(p, 0, 3, 1, 1, 112, 150):
+        return 8
+    if ell(p, (-8.5, 125, 0), (5, 12, 7)) or ell(p, (8.5, 125, 0), (5, 12, 7)):
+        return 3
+    if ell(p, (-7, 105, 0), (9, 7, 7)):
+        return 6
+    if ell(p, (8, 105, -2), (4, 6, 3)):
+        return 4
+    if ell(p, (-4, 79, 1), (2, 2, 2)) or ell(p, (4, 79, 1), (2, 2, 2)):
+        return 13
+    if ell(p, (0, 85, -3), (3.5, 3.5, 3.5)):
+        return 7
+    if 88 <= y < 98 and 0.45 <= (x / 11) ** 2 + (z / 6) ** 2 <= 1.0:
+        return 5
+    return 1
+
+def pos(i, j, k):
+    return (i - NX / 2 + 0.5, j + 0.5, k - NZ / 2 + 0.5)
+
+inside = [[[body(pos(i, j, k)) for k in range(NZ)] for j in range(NY)] for i in range(NX)]
+def is_in(i, j, k):
+    return 0 <= i < NX and 0 <= j < NY and 0 <= k < NZ and inside[i][j][k]
+
+data = bytearray()
+for k in range(NZ):
+    for j in range(NY):
+        for i in range(NX):
+            if not inside[i][j][k]:
+                data.append(0)
+            elif not all(is_in(i + a, j + b, k + c) for a, b, c in
+                     [(1, 0, 0), (-1, 0, 0), (0, 1, 0), (0, -1, 0), (0, 0, 1), (0, 0, -1)]):
+                data.append(2)
+            else:
+                data.append(organ(pos(i, j, k)))
+
+header = "# stylised human phantom with ICRP 103 tissues, generated by generate_human.py\n"
+header += "# all tissues are water with MIRD densities, front faces -z\n"
+header += f"dimensions {NX} {NY} {NZ}\nvoxel_size 0.01 0.01 0.01\nformat u8\n"
+for id, sub, name, _, _ in organs:
+    header += f"organ {id} {sub} {name}\n"
+for id, _, _, tissue, _ in organs:
+    if tissue:
+        header += f"tissue {id} {tissue}\n"
+for id, _, _, _, density in organs:
+    if density:
+        header += f"density {id} {density}\n"
+header += "data\n"
+path = os.path.join(os.path.dirname(os.path.abspath(__file__)), "human.phantom")
+with open(path, "wb") as file:
+    file.write(header.encode() + bytes(data))
+
+counts = Counter(data)
+for id, _, name, _, density in organs:
+    if density:
+        print(f"{name}: {counts[id]} voxels, {counts[id] * density / 1e6:.2f} kg")
@@ -0,0 +1,53 @@
+//! Lists the files of `assets/simulation_data` in `OUT_DIR/embedded_files.rs`, they are
+//! compiled into the binary and requested by the web version, which can't read directories.
+
+use std::{
+    env, fs,
+    io::Result,
+    path::{Path, PathBuf},
+};
+
+const DATA_DIRECTORY: &str = "assets/simulation_data";
+/// empty tables showing the format, not data
+const TEMPLATE_FILE: &str = "t.csv";
+
+fn main() -> Result<()> {
+    println!("cargo:rerun-if-changed={}", DATA_DIRECTORY);
+
+    let root = Path::new(DATA_DIRECTORY);
+    let mut files = Vec::new();
+    collect_csv_files(root, &mut files)?;
+
+    let mut paths = files
+        .iter()
+        .filter(|path| path.file_name().is_some_and(|name| name != TEMPLATE_FILE))
+        .map(|path| {
+            path.strip_prefix(root)
+                .unwrap()
+                .to_string_lossy()
+                .replace('\\', "/")
+        })
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    let mut list = "embed![\n".to_owned();
+    for path in paths {
+        list.push_str(&format!("    {:?},\n", path));
+    }
+    list.push_str("]\n");
+
+    let out_dir = PathBuf::from(env::var_os("OUT_DIR").unwrap());
+    fs::write(out_dir.join("embedded_files.rs"), list)
+}
+
+fn collect_csv_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_csv_files(&path, files)?;
+        } else if path.extension().is_some_and(|extension| extension == "csv") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
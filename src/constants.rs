@@ -7,4 +7,6 @@ lazy_static! {
     pub static ref EV_CONVERSION: f64 = 1.602 * (10f64).powi(-19);
     pub static ref ELECTRON_MASS: f64 = 9.109_384 * (10f64).powi(-31);
     pub static ref ALPHA_MASS: f64 = 6.644_657 * (10f64).powi(-27);
+    pub static ref PROTON_MASS: f64 = 1.672_622 * (10f64).powi(-27);
+    pub static ref NEUTRON_MASS: f64 = 1.674_927 * (10f64).powi(-27);
 }
@@ -0,0 +1,227 @@
+//! Analytic collision stopping powers from the Bethe–Bloch formula,
+//! used for elements without tabulated data.
+//! All values are in the units of the data files: energies in MeV, stopping powers in MeV*cm2/g.
+
+use crate::{ALPHA_MASS, ELECTRON_MASS, EV_CONVERSION, LIGHT_SPEED_SQ, PROTON_MASS};
+
+/// 4 pi N_A r_e^2 m_e c^2 in MeV*cm2/mol
+const K: f64 = 0.307_075;
+/// materials below this density in g/cm3 use the density correction parameters of gases
+const GAS_DENSITY: f64 = 0.01;
+
+/// in MeV
+pub fn rest_energy(mass: f64) -> f64 {
+    mass * LIGHT_SPEED_SQ / *EV_CONVERSION / 1_000_000.0
+}
+
+/// What the Bethe formula needs to know about an element
+#[derive(Debug, Clone, Copy)]
+pub struct BetheMaterial {
+    pub z: f64,
+    /// Z/A in mol/g
+    pub nucleon_ratio: f64,
+    /// mean excitation energy in eV
+    pub mean_excitation_energy: f64,
+    /// in g/cm3
+    pub density: f64,
+}
+
+impl BetheMaterial {
+    /// Sternheimer density effect correction for a given beta*gamma,
+    /// using the general parameters instead of tabulated ones
+    pub fn density_correction(&self, beta_gamma: f64) -> f64 {
+        // plasma energy in eV
+        let plasma_energy = 28.816 * (self.density * self.nucleon_ratio).sqrt();
+        if plasma_energy <= 0.0 {
+            return 0.0;
+        }
+        let c = 2.0 * (self.mean_excitation_energy / plasma_energy).ln() + 1.0;
+
+        let (x0, x1) = if self.density < GAS_DENSITY {
+            match c {
+                c if c < 10.0 => (1.6, 4.0),
+                c if c < 10.5 => (1.7, 4.0),
+                c if c < 11.0 => (1.8, 4.0),
+                c if c < 11.5 => (1.9, 4.0),
+                c if c < 12.25 => (2.0, 4.0),
+                c if c < 13.804 => (2.0, 5.0),
+                c => (0.326 * c - 2.5, 5.0),
+            }
+        } else if self.mean_excitation_energy < 100.0 {
+            (if c < 3.681 { 0.2 } else { 0.326 * c - 1.0 }, 2.0)
+        } else {
+            (if c < 5.215 { 0.2 } else { 0.326 * c - 1.5 }, 3.0)
+        };
+        let m = 3.0;
+        let a = (c - 4.606 * x0) / (x1 - x0).powf(m);
+
+        let x = beta_gamma.log10();
+        if x < x0 {
+            0.0
+        } else if x < x1 {
+            4.606 * x - c + a * (x1 - x).powf(m)
+        } else {
+            4.606 * x - c
+        }
+    }
+
+    /// Shell correction C, the formula is only valid for beta*gamma > 0.13,
+    /// below it is scaled down to vanish at rest
+    pub fn shell_correction(&self, beta_gamma: f64) -> f64 {
+        let eta = beta_gamma.max(0.13);
+        let i = self.mean_excitation_energy;
+        let correction = (0.422_377 * eta.powi(-2) + 0.030_404_3 * eta.powi(-4)
+            - 0.000_381_06 * eta.powi(-6))
+            * 1e-6
+            * i.powi(2)
+            + (3.858_019 * eta.powi(-2) - 0.166_798_9 * eta.powi(-4) + 0.001_579_55 * eta.powi(-6))
+                * 1e-9
+                * i.powi(3);
+        correction * (beta_gamma / eta).powi(2)
+    }
+
+    /// Radiation length in g/cm2
+    pub fn radiation_length(&self) -> f64 {
+        radiation_length(self.z, self.nucleon_ratio)
+    }
+}
+
+/// Radiation length of an element in g/cm2, `nucleon_ratio` is Z/A
+pub fn radiation_length(z: f64, nucleon_ratio: f64) -> f64 {
+    let a = z / nucleon_ratio;
+    716.4 * a / (z * (z + 1.0) * (287.0 / z.sqrt()).ln())
+}
+
+/// Collision stopping power of a heavy charged particle,
+/// `charge` in elementary charges, `rest_energy` and `energy` (kinetic) in MeV
+pub fn heavy_particle_stopping_power(
+    material: &BetheMaterial,
+    charge: f64,
+    rest_energy: f64,
+    energy: f64,
+) -> f64 {
+    let electron_rest_energy = self::rest_energy(*ELECTRON_MASS);
+    let gamma = 1.0 + energy / rest_energy;
+    let beta_sq = 1.0 - 1.0 / (gamma * gamma);
+    let beta_gamma = (beta_sq).sqrt() * gamma;
+
+    let mass_ratio = electron_rest_energy / rest_energy;
+    let max_transfer = 2.0 * electron_rest_energy * beta_gamma * beta_gamma
+        / (1.0 + 2.0 * gamma * mass_ratio + mass_ratio * mass_ratio);
+    let i = material.mean_excitation_energy / 1_000_000.0;
+
+    let log_term =
+        0.5 * (2.0 * electron_rest_energy * beta_gamma * beta_gamma * max_transfer / (i * i)).ln();
+
+    K * charge * charge * material.nucleon_ratio / beta_sq
+        * (log_term
+            - beta_sq
+            - material.density_correction(beta_gamma) / 2.0
+            - material.shell_correction(beta_gamma) / material.z)
+}
+
+/// Collision stopping power of an electron (Rohrlich–Carlson), `energy` (kinetic) in MeV
+pub fn electron_collision_stopping_power(material: &BetheMaterial, energy: f64) -> f64 {
+    let electron_rest_energy = rest_energy(*ELECTRON_MASS);
+    let tau = energy / electron_rest_energy;
+    let gamma = tau + 1.0;
+    let beta_sq = 1.0 - 1.0 / (gamma * gamma);
+    let beta_gamma = beta_sq.sqrt() * gamma;
+    let i = material.mean_excitation_energy / 1_000_000.0 / electron_rest_energy;
+
+    let f = 1.0 - beta_sq
+        + (tau * tau / 8.0 - (2.0 * tau + 1.0) * std::f64::consts::LN_2) / (gamma * gamma);
+
+    K / 2.0 * material.nucleon_ratio / beta_sq
+        * ((tau * tau * (tau + 2.0) / (2.0 * i * i)).ln() + f
+            - material.density_correction(beta_gamma))
+}
+
+/// Energy grid with `per_decade` log spaced points from `min` to `max` MeV
+pub(super) fn energy_grid(min: f64, max: f64, per_decade: usize) -> Vec<f64> {
+    let decades = (max / min).log10();
+    let points = (decades * per_decade as f64).round() as usize;
+    (0..=points)
+        .map(|i| min * 10f64.powf(decades * i as f64 / points as f64))
+        .collect()
+}
+
+/// Above 2 MeV per nucleon the Bethe formula is reliable (ICRU 49)
+const ALPHA_BETHE_ENERGY: f64 = 8.0;
+/// Energy in MeV at which the low energy branch equals `ALPHA_LOW_SCALE` times the
+/// Lindhard–Scharff Z dependence per atomic mass
+const ALPHA_LOW_REFERENCE_ENERGY: f64 = 0.01;
+/// in MeV*cm2/g, see `alpha_stopping_powers`
+const ALPHA_LOW_SCALE: f64 = 13_000.0;
+/// Energy exponent of the low energy branch, 0.5 would be proportional to the velocity
+const ALPHA_LOW_EXPONENT: f64 = 0.45;
+/// Energy exponent extrapolating the Bethe value at `ALPHA_BETHE_ENERGY` to lower energies
+const ALPHA_HIGH_EXPONENT: f64 = 0.8;
+
+/// Alpha stopping powers from 1 keV to 1 GeV like the ASTAR tables.
+///
+/// Bethe–Bloch fails at low energies. Below `ALPHA_BETHE_ENERGY` the stopping power is
+/// combined like in the Andersen–Ziegler fits (ICRU 49): 1/S = 1/S_low + 1/S_high.
+/// S_low has the Z dependence of the Lindhard–Scharff electronic stopping,
+/// Z2 / (Z1^2/3 + Z2^2/3)^3/2 per atomic mass, S_high is the Bethe value at
+/// `ALPHA_BETHE_ENERGY` extrapolated with a power law.
+/// The Andersen–Ziegler coefficients are tabulated per element, so instead the scale and the
+/// two exponents were fitted to the ASTAR tables of the 15 elements in the data directory
+/// (H to Pb) below 8 MeV. A least squares fit of the logarithm only lowers the RMS deviation
+/// from 27 % to 25 %, the form of the approximation limits the accuracy.
+///
+/// Compared to those tables, below 2 MeV H and He come out up to 2.7 times too high and the
+/// other elements are within -40 % to +75 %. From 2 to 8 MeV the values are within 10 %
+/// (17 % for Pb) and above 8 MeV within 6 % (15 % for Pb).
+pub fn alpha_stopping_powers(material: &BetheMaterial) -> Vec<(f32, f32)> {
+    let alpha_rest_energy = rest_energy(*ALPHA_MASS);
+    let bethe = |energy| heavy_particle_stopping_power(material, 2.0, alpha_rest_energy, energy);
+    let bethe_limit = bethe(ALPHA_BETHE_ENERGY);
+
+    let atomic_mass = material.z / material.nucleon_ratio;
+    let z1: f64 = 2.0;
+    let low_factor = ALPHA_LOW_SCALE * material.z
+        / (z1.powf(2.0 / 3.0) + material.z.powf(2.0 / 3.0)).powf(1.5)
+        / atomic_mass;
+
+    energy_grid(0.001, 1000.0, 20)
+        .into_iter()
+        .map(|energy| {
+            let low = low_factor * (energy / ALPHA_LOW_REFERENCE_ENERGY).powf(ALPHA_LOW_EXPONENT);
+            let high = if energy >= ALPHA_BETHE_ENERGY {
+                bethe(energy)
+            } else {
+                bethe_limit * (ALPHA_BETHE_ENERGY / energy).powf(ALPHA_HIGH_EXPONENT)
+            };
+            (energy as f32, (1.0 / (1.0 / low + 1.0 / high)) as f32)
+        })
+        .collect()
+}
+
+/// Proton stopping powers from alpha stopping powers at the same velocity,
+/// S_p(E) = S_a(E m_a / m_p) / 4 with the ratio of the squared charges.
+/// Slow alphas carry electrons and have an effective charge below 2, so below about
+/// 1 MeV the proton values are too low. Works with any units.
+pub fn proton_stopping_powers(alpha_stopping_powers: &[(f32, f32)]) -> Vec<(f32, f32)> {
+    let mass_ratio = (*PROTON_MASS / *ALPHA_MASS) as f32;
+    alpha_stopping_powers
+        .iter()
+        .map(|(energy, stopping_power)| (energy * mass_ratio, stopping_power / 4.0))
+        .collect()
+}
+
+/// Total electron stopping powers from 10 keV to 1 GeV like the ESTAR tables.
+/// The radiative part is approximated with the radiation length.
+pub fn electron_stopping_powers(material: &BetheMaterial) -> Vec<(f32, f32)> {
+    let electron_rest_energy = rest_energy(*ELECTRON_MASS);
+    let radiation_length = material.radiation_length();
+
+    energy_grid(0.01, 1000.0, 20)
+        .into_iter()
+        .map(|energy| {
+            let collision = electron_collision_stopping_power(material, energy);
+            let radiative = (energy + electron_rest_energy) / radiation_length;
+            (energy as f32, (collision + radiative) as f32)
+        })
+        .collect()
+}
@@ -1,12 +1,13 @@
-use std::collections::HashMap;
-use std::io::Cursor;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 
 use serde::Deserialize;
 
-use crate::{ParticleType, StoppingPower};
+use crate::{material::interpolate_stopping_power, ParticleType, StoppingPower};
 
-use super::{parse_num, MassAttenuationCoefficientRow, StoppingPowerRow};
+use super::{bethe, Element, Isotope};
+
+use super::{tables::ValidateRow, TableKey, TableSet};
 
 #[derive(Debug, Clone, Default)]
 pub struct Compound {
@@ -15,16 +16,344 @@ pub struct Compound {
     /// in kg/m3
     pub density: f32,
     pub stopping_powers: HashMap<ParticleType, StoppingPower>,
+    /// (z, mass fraction), empty for compounds with tabulated data
+    pub composition: Vec<(usize, f32)>,
+    /// what the compound was created from, the fractions are normalized
+    pub constituents: Vec<Constituent>,
+    /// the isotopes that can decay with their activity in Bq per kg of the compound
+    pub radioactive_isotopes: Vec<(Isotope, f32)>,
+    /// particles with calculated data, for formula compounds in at least one of the elements
+    pub approximated: Vec<ParticleType>,
 
     pub is_absorber: bool,
 }
 
-pub fn get_compounds() -> Vec<Arc<Compound>> {
-    let compound_data = get_compound_data();
+/// (z, mass number of a specific isotope or `None` for the natural element, mass fraction)
+pub type Constituent = (usize, Option<usize>, f32);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompoundError {
+    InvalidFormula(String),
+    UnknownElement(String),
+    UnknownIsotope(String),
+    /// element has no stopping power data for all particles
+    MissingData(String),
+    DuplicateName(String),
+    Empty,
+}
+
+impl std::fmt::Display for CompoundError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidFormula(formula) => {
+                f.write_fmt(format_args!("invalid formula: {}", formula))
+            }
+            Self::UnknownElement(symbol) => {
+                f.write_fmt(format_args!("unknown element: {}", symbol))
+            }
+            Self::UnknownIsotope(symbol) => {
+                f.write_fmt(format_args!("unknown isotope: {}", symbol))
+            }
+            Self::MissingData(symbol) => {
+                f.write_fmt(format_args!("no stopping power data for {}", symbol))
+            }
+            Self::DuplicateName(name) => f.write_fmt(format_args!("{} already exists", name)),
+            Self::Empty => f.write_str("compound has no elements"),
+        }
+    }
+}
+
+impl std::error::Error for CompoundError {}
+
+impl Compound {
+    /// Create a compound from a chemical formula like `C5H8O2` or `Ca10(PO4)6(OH)2`,
+    /// specific isotopes are written in brackets like `H[3H]O`. Density in kg/m3.
+    pub fn from_formula(
+        name: &str,
+        formula: &str,
+        density: f32,
+        elements: &BTreeMap<usize, Arc<Element>>,
+    ) -> Result<Self, CompoundError> {
+        let atoms = parse_formula(formula, elements)?;
+
+        // isotopes weigh about their mass number
+        let atom_mass = |z: usize, mass_number: Option<usize>| {
+            mass_number.map_or_else(|| elements[&z].atomic_mass(), |a| a as f32)
+        };
+        let total_mass: f32 = atoms
+            .iter()
+            .map(|(z, mass_number, count)| atom_mass(*z, *mass_number) * count)
+            .sum();
+        let mass_fractions = atoms
+            .iter()
+            .map(|(z, mass_number, count)| {
+                (
+                    *z,
+                    *mass_number,
+                    atom_mass(*z, *mass_number) * count / total_mass,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        Self::from_mass_fractions(name, formula, &mass_fractions, density, elements)
+    }
+
+    /// Create a compound from its constituents using Bragg additivity.
+    /// Fractions are normalized, density in kg/m3.
+    /// Natural elements bring their radioactive isotopes in their natural abundance.
+    pub fn from_mass_fractions(
+        name: &str,
+        symbol: &str,
+        mass_fractions: &[Constituent],
+        density: f32,
+        elements: &BTreeMap<usize, Arc<Element>>,
+    ) -> Result<Self, CompoundError> {
+        let total: f32 = mass_fractions.iter().map(|(_, _, fraction)| fraction).sum();
+        if mass_fractions.is_empty() || total <= 0.0 {
+            return Err(CompoundError::Empty);
+        }
+
+        let mut composition: Vec<(usize, f32)> = Vec::new();
+        let mut constituents = Vec::new();
+        let mut radioactive_isotopes = Vec::new();
+        let mut approximated = Vec::new();
+        for (z, mass_number, fraction) in mass_fractions {
+            let element = elements
+                .get(z)
+                .ok_or_else(|| CompoundError::UnknownElement(z.to_string()))?;
+            if !element.is_absorber {
+                return Err(CompoundError::MissingData(element.symbol.clone()));
+            }
+            constituents.push((*z, *mass_number, fraction / total));
+
+            let isotopes = match mass_number {
+                Some(a) => {
+                    let isotope = a
+                        .checked_sub(*z)
+                        .and_then(|n| element.isotopes.get(&n))
+                        .ok_or_else(|| {
+                            CompoundError::UnknownIsotope(format!("{}{}", a, element.symbol))
+                        })?;
+                    vec![(isotope, 1.0)]
+                }
+                None => element.natural_isotopes(),
+            };
+            for (isotope, isotope_fraction) in isotopes {
+                if let (true, Some(activity)) = (isotope.is_usable, isotope.activity) {
+                    radioactive_isotopes.push((
+                        isotope.clone(),
+                        activity * isotope_fraction * fraction / total,
+                    ));
+                }
+            }
+            for particle_type in &element.approximated {
+                if !approximated.contains(particle_type) {
+                    approximated.push(*particle_type);
+                }
+            }
+            match composition.iter_mut().find(|(other, _)| other == z) {
+                Some((_, existing)) => *existing += fraction / total,
+                None => composition.push((*z, fraction / total)),
+            }
+        }
+
+        let mut stopping_powers = HashMap::new();
+        for particle_type in [
+            ParticleType::Alpha,
+            ParticleType::Electron,
+            ParticleType::Proton,
+            ParticleType::Neutron,
+            ParticleType::Gamma,
+        ] {
+            let tables = composition
+                .iter()
+                .map(|(z, fraction)| (*fraction, &elements[z].stopping_powers[&particle_type]))
+                .collect::<Vec<_>>();
+
+            let mut energies = tables
+                .iter()
+                .flat_map(|(_, table)| table.iter().map(|(energy, _)| *energy))
+                .collect::<Vec<_>>();
+            energies.sort_by(|a, b| a.total_cmp(b));
+            energies.dedup();
+
+            stopping_powers.insert(
+                particle_type,
+                energies
+                    .into_iter()
+                    .map(|energy| {
+                        let mass_stopping_power: f32 = tables
+                            .iter()
+                            .map(|(weight, table)| {
+                                weight * interpolate_stopping_power(table, energy)
+                            })
+                            .sum();
+                        (energy, mass_stopping_power)
+                    })
+                    .collect(),
+            );
+        }
+
+        Ok(Compound {
+            symbol: symbol.to_owned(),
+            name: name.to_owned(),
+            density,
+            stopping_powers,
+            composition,
+            constituents,
+            radioactive_isotopes,
+            approximated,
+            is_absorber: true,
+        })
+    }
+}
+
+/// Parse an element symbol like `U` or an isotope with its mass number like `238U`,
+/// optionally in brackets like `[3H]`, into z and the mass number
+pub fn parse_nuclide(
+    nuclide: &str,
+    elements: &BTreeMap<usize, Arc<Element>>,
+) -> Result<(usize, Option<usize>), CompoundError> {
+    let nuclide = nuclide.trim();
+    let nuclide = nuclide
+        .strip_prefix('[')
+        .and_then(|inner| inner.strip_suffix(']'))
+        .unwrap_or(nuclide);
+    let symbol = nuclide.trim_start_matches(|c: char| c.is_ascii_digit());
+    let mass_number = match &nuclide[..nuclide.len() - symbol.len()] {
+        "" => None,
+        digits => Some(
+            digits
+                .parse()
+                .map_err(|_| CompoundError::InvalidFormula(nuclide.to_owned()))?,
+        ),
+    };
+    let z = elements
+        .values()
+        .find(|element| element.symbol == symbol)
+        .ok_or_else(|| CompoundError::UnknownElement(symbol.to_owned()))?
+        .z;
+    Ok((z, mass_number))
+}
+
+/// Parse a chemical formula into (z, mass number of a specific isotope, number of atoms),
+/// groups in parentheses can have a count and isotopes are written in brackets like `[3H]`.
+pub fn parse_formula(
+    formula: &str,
+    elements: &BTreeMap<usize, Arc<Element>>,
+) -> Result<Vec<(usize, Option<usize>, f32)>, CompoundError> {
+    let invalid = || CompoundError::InvalidFormula(formula.to_owned());
+    let chars = formula
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<Vec<_>>();
+
+    // stack of groups, the last one is the currently open group
+    let mut groups: Vec<Vec<(usize, Option<usize>, f32)>> = vec![Vec::new()];
+    let mut i = 0;
+
+    let read_count = |i: &mut usize| -> Result<f32, CompoundError> {
+        let start = *i;
+        while *i < chars.len() && (chars[*i].is_ascii_digit() || chars[*i] == '.') {
+            *i += 1;
+        }
+        if start == *i {
+            Ok(1.0)
+        } else {
+            chars[start..*i]
+                .iter()
+                .collect::<String>()
+                .parse()
+                .map_err(|_| invalid())
+        }
+    };
+
+    while i < chars.len() {
+        match chars[i] {
+            '(' => {
+                groups.push(Vec::new());
+                i += 1;
+            }
+            ')' => {
+                i += 1;
+                let count = read_count(&mut i)?;
+                let group = groups.pop().ok_or_else(invalid)?;
+                let parent = groups.last_mut().ok_or_else(invalid)?;
+                parent.extend(
+                    group
+                        .into_iter()
+                        .map(|(z, mass_number, n)| (z, mass_number, n * count)),
+                );
+            }
+            '[' => {
+                let start = i;
+                while i < chars.len() && chars[i] != ']' {
+                    i += 1;
+                }
+                if i == chars.len() {
+                    return Err(invalid());
+                }
+                i += 1;
+                let (z, mass_number) =
+                    parse_nuclide(&chars[start..i].iter().collect::<String>(), elements)?;
+                if mass_number.is_none() {
+                    return Err(invalid());
+                }
+                let count = read_count(&mut i)?;
+                groups.last_mut().unwrap().push((z, mass_number, count));
+            }
+            c if c.is_ascii_uppercase() => {
+                let start = i;
+                i += 1;
+                while i < chars.len() && chars[i].is_ascii_lowercase() {
+                    i += 1;
+                }
+                let symbol = chars[start..i].iter().collect::<String>();
+                let z = elements
+                    .values()
+                    .find(|element| element.symbol == symbol)
+                    .ok_or(CompoundError::UnknownElement(symbol))?
+                    .z;
+                let count = read_count(&mut i)?;
+                groups.last_mut().unwrap().push((z, None, count));
+            }
+            _ => return Err(invalid()),
+        }
+    }
+
+    if groups.len() != 1 {
+        return Err(invalid());
+    }
+
+    let mut atoms: Vec<(usize, Option<usize>, f32)> = Vec::new();
+    for (z, mass_number, count) in groups.pop().unwrap() {
+        match atoms
+            .iter_mut()
+            .find(|(other, other_mass_number, _)| *other == z && *other_mass_number == mass_number)
+        {
+            Some((_, _, existing)) => *existing += count,
+            None => atoms.push((z, mass_number, count)),
+        }
+    }
+
+    if atoms.is_empty() {
+        return Err(CompoundError::Empty);
+    }
+    Ok(atoms)
+}
+
+/// Compounds with tabulated data. Protons are scaled from the alpha table, neutrons and the
+/// composition use the symbol as formula. Compounds whose symbol isn't a formula don't stop
+/// neutrons and absorb photons at the first interaction.
+pub fn get_compounds(
+    tables: &TableSet,
+    elements: &BTreeMap<usize, Arc<Element>>,
+) -> Vec<Arc<Compound>> {
+    let compound_data = tables.compounds.clone();
 
-    let mut alpha_stopping_power = get_stopping_power(ParticleType::Alpha);
-    let mut electron_stopping_power = get_stopping_power(ParticleType::Electron);
-    let mut gamma_stopping_power = get_gamma_stopping_power();
+    let mut alpha_stopping_power = tables.stopping_power_tables(ParticleType::Alpha);
+    let mut electron_stopping_power = tables.stopping_power_tables(ParticleType::Electron);
+    let mut gamma_stopping_power = tables.stopping_power_tables(ParticleType::Gamma);
 
     compound_data
         .into_iter()
@@ -35,58 +364,70 @@ pub fn get_compounds() -> Vec<Arc<Compound>> {
             // stopping powers
             let mut stopping_powers = HashMap::new();
 
-            let is_absorber = alpha_stopping_power.contains_key(&compound.name)
-                && electron_stopping_power.contains_key(&compound.name)
-                && gamma_stopping_power.contains_key(&compound.name);
+            let key = TableKey::Compound(compound.name.clone());
+            let is_absorber = alpha_stopping_power.contains_key(&key)
+                && electron_stopping_power.contains_key(&key)
+                && gamma_stopping_power.contains_key(&key);
 
+            // mass stopping powers, the density is applied when they are used
             // 1 cm2/g = 0.1 m2/kg =>
             // 1 MeV*cm2/g = 100_000 eV*m2/kg
-            // 1 eV*m2/kg * 1 kg/m3 = 1 eV/m
-            if let Some(a) = alpha_stopping_power.remove(&compound.name) {
-                stopping_powers.insert(
-                    ParticleType::Alpha,
-                    a.into_iter()
-                        .map(|(energy, stop_power)| {
-                            (energy * 1_000_000.0, stop_power * 100_000.0 * density)
-                        })
-                        .collect(),
-                );
+            if let Some(a) = alpha_stopping_power.remove(&key) {
+                let a: StoppingPower = a
+                    .into_iter()
+                    .map(|(energy, stop_power)| (energy * 1_000_000.0, stop_power * 100_000.0))
+                    .collect();
+                stopping_powers.insert(ParticleType::Proton, bethe::proton_stopping_powers(&a));
+                stopping_powers.insert(ParticleType::Alpha, a);
             }
-            if let Some(e) = electron_stopping_power.remove(&compound.name) {
+            if let Some(e) = electron_stopping_power.remove(&key) {
                 stopping_powers.insert(
                     ParticleType::Electron,
                     e.into_iter()
-                        .map(|(energy, stop_power)| {
-                            (energy * 1_000_000.0, stop_power * 100_000.0 * density)
-                        })
+                        .map(|(energy, stop_power)| (energy * 1_000_000.0, stop_power * 100_000.0))
                         .collect(),
                 );
             }
             // 1 cm2/g = 0.1 m2/kg
-            // 1 m2/kg * 1 kg/m3 = 1/m
-            if let Some(g) = gamma_stopping_power.remove(&compound.name) {
+            if let Some(g) = gamma_stopping_power.remove(&key) {
                 stopping_powers.insert(
                     ParticleType::Gamma,
                     g.into_iter()
-                        .map(|(energy, stop_power)| {
-                            (energy * 1_000_000.0, stop_power * 0.1 * density)
-                        })
+                        .map(|(energy, stop_power)| (energy * 1_000_000.0, stop_power * 0.1))
                         .collect(),
                 );
             }
+            let mut formula =
+                Compound::from_formula(&compound.name, &compound.symbol, density, elements).ok();
+            if let Some(neutron) = formula
+                .as_mut()
+                .and_then(|formula| formula.stopping_powers.remove(&ParticleType::Neutron))
+            {
+                stopping_powers.insert(ParticleType::Neutron, neutron);
+            }
+
+            // protons are scaled from the alpha table, neutrons use removal cross sections
+            let approximated = [ParticleType::Proton, ParticleType::Neutron]
+                .into_iter()
+                .filter(|particle_type| stopping_powers.contains_key(particle_type))
+                .collect();
 
             Arc::new(Compound {
                 symbol: compound.symbol,
                 name: compound.name,
                 density,
                 stopping_powers,
+                composition: formula.map_or_else(Vec::new, |formula| formula.composition),
+                constituents: Vec::new(),
+                radioactive_isotopes: Vec::new(),
+                approximated,
                 is_absorber,
             })
         })
         .collect()
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct CompoundDataRow {
     pub symbol: String,
     pub name: String,
@@ -97,91 +438,96 @@ pub struct CompoundDataRow {
     pub density: f32,
 }
 
-fn get_compound_data() -> Vec<CompoundDataRow> {
-    let mut data_reader = csv::Reader::from_reader(Cursor::new(include_bytes!(
-        "./../../assets/simulation_data/compound_data.csv"
-    )));
-    data_reader
-        .deserialize()
-        .filter_map(|row| row.ok())
-        .collect()
+impl ValidateRow for CompoundDataRow {
+    fn validate(&self) -> Result<(), (&'static str, String)> {
+        if !self.density.is_finite() || self.density < 0.0 {
+            return Err(("density", format!("{} g/cm3 is not valid", self.density)));
+        }
+        Ok(())
+    }
 }
 
-// technically this is a mass attenuation coeffients but data reading and storing is similar
-fn get_stopping_power(particle_type: ParticleType) -> HashMap<String, Vec<(f32, f32)>> {
-    #[rustfmt::skip]
-    let table_data = match particle_type {
-        ParticleType::Alpha => {vec![
-            ("Air", include_str!("./../../assets/simulation_data/stopping_power_alpha/Air.csv")),
-            ("Water", include_str!("./../../assets/simulation_data/stopping_power_alpha/Water.csv")),
-            ("Vacuum", include_str!("./../../assets/simulation_data/stopping_power_alpha/Vacuum.csv")),
-        ]}
-        ParticleType::Electron => {vec![
-            ("Air", include_str!("./../../assets/simulation_data/stopping_power_electrons/Air.csv")),
-            ("Water", include_str!("./../../assets/simulation_data/stopping_power_electrons/Water.csv")),
-            ("Vacuum", include_str!("./../../assets/simulation_data/stopping_power_electrons/Vacuum.csv")),
-        ]}
-        _ => panic!("requested stopping power table for not registered particle"),
-    };
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let mut stopping_powers = HashMap::new();
-
-    for (name, data) in table_data {
-        let mut data_reader = csv::Reader::from_reader(Cursor::new(data));
-        stopping_powers.insert(
-            name.to_owned(),
-            data_reader
-                .deserialize()
-                .filter_map(|row| {
-                    row.map_err(|e| {
-                        log::warn!("Error reading row ({}, a/e): {}", &name, e);
-                        e
-                    })
-                    .ok()
-                })
-                .map(|row: StoppingPowerRow| {
-                    (
-                        parse_num(row.energy.as_str()),
-                        parse_num(row.stop_power.as_str()),
-                    )
-                })
-                .collect(),
+    fn elements() -> BTreeMap<usize, Arc<Element>> {
+        [
+            (1, "H"),
+            (6, "C"),
+            (8, "O"),
+            (15, "P"),
+            (20, "Ca"),
+            (92, "U"),
+        ]
+        .into_iter()
+        .map(|(z, symbol)| {
+            let element = Element {
+                z,
+                symbol: symbol.to_owned(),
+                ..Default::default()
+            };
+            (z, Arc::new(element))
+        })
+        .collect()
+    }
+
+    #[test]
+    fn simple_formula() {
+        assert_eq!(
+            parse_formula("CaCO3", &elements()).unwrap(),
+            vec![(20, None, 1.0), (6, None, 1.0), (8, None, 3.0)]
         );
     }
 
-    stopping_powers
-}
+    #[test]
+    fn nested_groups() {
+        assert_eq!(
+            parse_formula("Ca10(PO4)6(OH)2", &elements()).unwrap(),
+            vec![
+                (20, None, 10.0),
+                (15, None, 6.0),
+                (8, None, 26.0),
+                (1, None, 2.0)
+            ]
+        );
+    }
 
-// technically this is a mass attenuation coeffients but data reading and storing is similar
-fn get_gamma_stopping_power() -> HashMap<String, Vec<(f32, f32)>> {
-    #[rustfmt::skip]
-    let table_data = vec![
-        ("Air", include_str!("./../../assets/simulation_data/mass_attenuation_coefficients/Air.csv")),
-        ("Water", include_str!("./../../assets/simulation_data/mass_attenuation_coefficients/Water.csv")),
-        ("Vacuum", include_str!("./../../assets/simulation_data/mass_attenuation_coefficients/Vacuum.csv")),
-    ];
-
-    let mut stopping_powers = HashMap::new();
-
-    for (name, data) in table_data {
-        let mut data_reader = csv::Reader::from_reader(Cursor::new(data));
-        stopping_powers.insert(
-            name.to_owned(),
-            data_reader
-                .deserialize()
-                .filter_map(|row| {
-                    row.map_err(|e| {
-                        log::warn!("Error reading row ({}, g): {}", &name, e);
-                        e
-                    })
-                    .ok()
-                })
-                .map(|row: MassAttenuationCoefficientRow| {
-                    (parse_num(row.energy.as_str()), parse_num(row.yp.as_str()))
-                })
-                .collect(),
+    #[test]
+    fn bracketed_isotope() {
+        assert_eq!(
+            parse_formula("H[3H]O", &elements()).unwrap(),
+            vec![(1, None, 1.0), (1, Some(3), 1.0), (8, None, 1.0)]
         );
     }
 
-    stopping_powers
+    #[test]
+    fn invalid_formulas() {
+        let elements = elements();
+        assert!(matches!(
+            parse_formula("Ca(OH", &elements),
+            Err(CompoundError::InvalidFormula(_))
+        ));
+        assert!(matches!(
+            parse_formula("[H]2O", &elements),
+            Err(CompoundError::InvalidFormula(_))
+        ));
+        assert!(matches!(
+            parse_formula("XeF2", &elements),
+            Err(CompoundError::UnknownElement(_))
+        ));
+        assert!(matches!(
+            parse_formula("", &elements),
+            Err(CompoundError::Empty)
+        ));
+    }
+
+    #[test]
+    fn nuclides() {
+        let elements = elements();
+        assert_eq!(parse_nuclide("U", &elements).unwrap(), (92, None));
+        assert_eq!(parse_nuclide("238U", &elements).unwrap(), (92, Some(238)));
+        assert_eq!(parse_nuclide(" [3H] ", &elements).unwrap(), (1, Some(3)));
+        assert!(parse_nuclide("3X", &elements).is_err());
+    }
 }
@@ -1,12 +1,17 @@
 use std::collections::{BTreeMap, HashMap};
-use std::io::Cursor;
 use std::sync::Arc;
 
 use serde::Deserialize;
 
 use crate::{constants::*, ParticleType, StoppingPower};
 
-use super::{parse_num, MassAttenuationCoefficientRow, StoppingPowerRow};
+use super::{
+    bethe::{self, BetheMaterial},
+    neutron, parse_num, photon,
+    relaxation::AtomicShells,
+    tables::{optional_num, require_num, ValidateRow, DECAY_RADIATION_DIRECTORY},
+    DataError, TableKey, TableSet,
+};
 
 #[derive(Debug, Clone, Default)]
 pub struct Element {
@@ -20,10 +25,44 @@ pub struct Element {
     pub density: f32,
     pub isotopes: BTreeMap<usize, Isotope>,
     pub stopping_powers: HashMap<ParticleType, StoppingPower>,
+    /// particles whose stopping powers or attenuation coefficients were calculated
+    /// instead of read from a table, protons and neutrons are always calculated
+    pub approximated: Vec<ParticleType>,
 
     pub is_absorber: bool,
 }
 
+impl Element {
+    /// in u, derived from the nucleon ratio (Z/A)
+    pub fn atomic_mass(&self) -> f32 {
+        self.z as f32 / self.nucleon_ratio
+    }
+
+    /// The isotopes found in nature with their mass fraction, empty for synthetic elements.
+    /// The fractions are weighted with the mass number, which is accurate to about 0.1 %.
+    pub fn natural_isotopes(&self) -> Vec<(&Isotope, f32)> {
+        let mass_number = |isotope: &Isotope| (isotope.z + isotope.n) as f32;
+        let total_mass: f32 = self
+            .isotopes
+            .values()
+            .map(|isotope| isotope.abundance.0 * mass_number(isotope))
+            .sum();
+        if total_mass <= 0.0 {
+            return Vec::new();
+        }
+        self.isotopes
+            .values()
+            .filter(|isotope| isotope.abundance.0 > 0.0)
+            .map(|isotope| {
+                (
+                    isotope,
+                    isotope.abundance.0 * mass_number(isotope) / total_mass,
+                )
+            })
+            .collect()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Isotope {
     pub z: usize,
@@ -35,6 +74,8 @@ pub struct Isotope {
     /// in u
     pub atomic_mass: f32,
     pub decays: Vec<Decay>,
+    /// gamma and X-ray lines, the intensities are per decay of the isotope
+    pub photon_lines: Vec<PhotonLine>,
     /// in Bq/kg
     pub activity: Option<f32>,
     pub is_usable: bool,
@@ -45,28 +86,392 @@ pub struct Decay {
     pub decay_type: DecayType,
     /// in eV
     pub decay_energy: f32,
-    /// in eV
-    pub gamma_energy: Option<f32>,
+    /// share of the decays of the isotope
+    pub intensity: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhotonType {
+    Gamma,
+    XRay,
 }
 
 #[derive(Debug, Clone)]
+pub struct PhotonLine {
+    pub photon_type: PhotonType,
+    /// in eV
+    pub energy: f32,
+    /// photons per decay
+    pub intensity: f32,
+    /// internal conversion coefficient, conversion electrons per photon
+    pub conversion_coefficient: f32,
+    /// atomic number of the atom whose excited nucleus emits the line,
+    /// conversion electrons come from its shells
+    pub daughter_z: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DecayType {
     BetaMinus,
     BetaPlus,
     BetaElectronCapture,
     Alpha,
+    /// EC without a positron, only the neutrino carries the energy
+    ElectronCapture,
+    /// two electrons or positrons share the decay energy
+    DoubleBeta,
+    IsomericTransition,
+    SpontaneousFission,
+    /// beta decay followed by the emission of neutrons
+    BetaDelayedNeutrons(u8),
+    /// EC or beta decay followed by the emission of protons
+    BetaDelayedProtons(u8),
+    /// beta decay or EC followed by the emission of an alpha
+    BetaDelayedAlpha,
+    Neutrons(u8),
+    Protons(u8),
+    /// emission of a nucleus heavier than an alpha like C-14 or Ne-24
+    ClusterEmission,
     Other,
 }
 
-pub fn get_elements() -> Vec<Arc<Element>> {
-    let element_data = get_element_data();
-    let isotope_data = get_isotope_data();
+impl DecayType {
+    /// Parse a decay mode of the isotope table like "B-", "ECP" or "B-2N", blank means no decay
+    pub fn from_code(code: &str) -> Option<DecayType> {
+        let code = code.trim();
+        // "B-2N" has the count in front of the emitted particle, "B-N" emits one
+        let count = |code: Option<&str>| {
+            code.and_then(|digits| {
+                if digits.is_empty() {
+                    Some(1)
+                } else {
+                    digits.parse::<u8>().ok()
+                }
+            })
+        };
+        let delayed = |particle: char| {
+            ["B-", "B+", "EC"]
+                .into_iter()
+                .find_map(|beta| count(code.strip_prefix(beta)?.strip_suffix(particle)))
+        };
+
+        let decay_type = match code {
+            "" => return None,
+            "B-" => DecayType::BetaMinus,
+            "B+" => DecayType::BetaPlus,
+            "EC+B+" | "B++EC" => DecayType::BetaElectronCapture,
+            "A" => DecayType::Alpha,
+            "EC" | "2EC" => DecayType::ElectronCapture,
+            "2B-" | "2B+" => DecayType::DoubleBeta,
+            "IT" => DecayType::IsomericTransition,
+            "B-A" | "B+A" | "ECA" => DecayType::BetaDelayedAlpha,
+            "ECP+EC2P" => DecayType::BetaDelayedProtons(1),
+            // "SF", "ECSF", "B-F" and mixed modes like "SF+EC+B+"
+            _ if code.contains("SF") || code.ends_with('F') || code.contains("fission") => {
+                DecayType::SpontaneousFission
+            }
+            _ => {
+                if let Some(count) = delayed('N') {
+                    DecayType::BetaDelayedNeutrons(count)
+                } else if let Some(count) = delayed('P') {
+                    DecayType::BetaDelayedProtons(count)
+                } else if let Some(count) = count(code.strip_suffix('N')) {
+                    DecayType::Neutrons(count)
+                } else if let Some(count) = count(code.strip_suffix('P')) {
+                    DecayType::Protons(count)
+                } else if code.starts_with(|c: char| c.is_ascii_digit() || c == '{')
+                    || (code.len() <= 2 && code.starts_with(char::is_uppercase))
+                {
+                    // "14C", "24NE", "{+24}Ne" or just "Mg"
+                    DecayType::ClusterEmission
+                } else {
+                    DecayType::Other
+                }
+            }
+        };
+        Some(decay_type)
+    }
+
+    /// Atomic number of the atom after the decay of an isotope with `z`, whose excited
+    /// nucleus emits the photon lines. Modes without a known direction keep `z`.
+    pub fn daughter_z(&self, z: usize) -> usize {
+        match self {
+            DecayType::BetaMinus | DecayType::BetaDelayedNeutrons(_) => z + 1,
+            DecayType::BetaPlus | DecayType::BetaElectronCapture | DecayType::ElectronCapture => {
+                z.saturating_sub(1)
+            }
+            DecayType::Alpha => z.saturating_sub(2),
+            DecayType::Protons(count) => z.saturating_sub(*count as usize),
+            DecayType::BetaDelayedProtons(count) => z.saturating_sub(1 + *count as usize),
+            _ => z,
+        }
+    }
+}
+
+/// the rows are validated when they are read, parsing can't fail anymore
+fn num(value: &str) -> f32 {
+    parse_num(value).unwrap_or_default()
+}
+
+/// Atomic number after the most frequent decay branch, lines without a known emitter
+/// are assigned to it
+fn main_daughter_z(z: usize, decays: &[Decay]) -> usize {
+    decays
+        .iter()
+        .max_by(|a, b| a.intensity.total_cmp(&b.intensity))
+        .map_or(z, |decay| decay.decay_type.daughter_z(z))
+}
+
+/// Decay branches and photon lines of an isotope from the files in `decay_radiations/`.
+/// Conversion electrons are assigned to the gamma line of their transition, which
+/// gets their intensity per photon as conversion coefficient. The descriptions of
+/// conversion electrons without a gamma line are returned, Auger electrons are left to
+/// the relaxation of the atom.
+fn decay_radiations(
+    tables: &TableSet,
+    z: usize,
+    n: usize,
+) -> (Vec<Decay>, Vec<PhotonLine>, Vec<String>) {
+    let mut decays = Vec::new();
+    // lines with the daughter of the export, if it has one
+    let mut photon_lines = Vec::new();
+    let mut conversion_electrons = Vec::new();
+    for row in tables
+        .decay_radiations
+        .values()
+        .flatten()
+        .filter(|row| row.p_z == z && row.p_n == n)
+    {
+        // keV to eV, % to share of the decays
+        let energy = num(row.energy()) * 1_000.0;
+        let intensity = num(&row.intensity) / 100.0;
+        let decay_type = match row.rad_type.as_str() {
+            "a" => DecayType::Alpha,
+            "bm" => DecayType::BetaMinus,
+            "bp" => DecayType::BetaPlus,
+            "g" | "x" => {
+                let photon_type = if row.rad_type == "x" {
+                    PhotonType::XRay
+                } else {
+                    PhotonType::Gamma
+                };
+                photon_lines.push((photon_type, energy, intensity, row.d_z));
+                continue;
+            }
+            _ => {
+                if row.is_conversion_electron() {
+                    conversion_electrons.push((row, energy, intensity));
+                }
+                continue;
+            }
+        };
+        decays.push(Decay {
+            decay_type,
+            decay_energy: energy,
+            intensity,
+        });
+    }
 
-    let mut alpha_stopping_power = get_stopping_power(ParticleType::Alpha);
-    let mut electron_stopping_power = get_stopping_power(ParticleType::Electron);
-    let mut gamma_stopping_power = get_gamma_stopping_power();
+    let main_daughter_z = main_daughter_z(z, &decays);
+    let mut photon_lines: Vec<_> = photon_lines
+        .into_iter()
+        .map(|(photon_type, energy, intensity, daughter_z)| PhotonLine {
+            photon_type,
+            energy,
+            intensity,
+            conversion_coefficient: 0.0,
+            daughter_z: daughter_z.unwrap_or(main_daughter_z),
+        })
+        .collect();
+
+    let mut unassigned = Vec::new();
+    for (row, energy, intensity) in conversion_electrons {
+        let daughter_z = row.d_z.unwrap_or(main_daughter_z);
+        let shells = AtomicShells::new(daughter_z);
+        let binding_energies = match row.shell() {
+            Some('K') => vec![shells.k_binding],
+            Some('L') => vec![shells.l_binding],
+            Some(_) => vec![shells.m_binding],
+            None => vec![shells.k_binding, shells.l_binding, shells.m_binding],
+        };
+        // the parametrised binding energies are only a few % off
+        let tolerance = 1_000.0 + 0.1 * binding_energies[0];
+        // the gamma line whose energy minus a binding energy is the closest
+        let line = photon_lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| {
+                line.photon_type == PhotonType::Gamma
+                    && line.daughter_z == daughter_z
+                    && line.intensity > 0.0
+            })
+            .flat_map(|(i, line)| {
+                binding_energies
+                    .iter()
+                    .map(move |binding| (i, (line.energy - binding - energy).abs()))
+            })
+            .filter(|(_, difference)| *difference < tolerance)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(i, _)| &mut photon_lines[i]);
+        match line {
+            Some(line) => line.conversion_coefficient += intensity / line.intensity,
+            None => unassigned.push(format!(
+                "{} electron of {} keV",
+                row.electron_type.trim(),
+                energy / 1_000.0
+            )),
+        }
+    }
+    (decays, photon_lines, unassigned)
+}
+
+/// The decay modes of the isotope table with their share of the decays and the modes that
+/// were left out because their energy is unknown. The table only has the decay energy of the
+/// first mode, the others take their Q value from the mass excesses. Isotopes without a decay
+/// energy are kept as they are, they can't be used anyway.
+fn table_decays(
+    isotope: &IsotopeDataRow,
+    mass_excesses: &HashMap<(usize, usize), f64>,
+) -> (Vec<Decay>, Vec<DecayType>) {
+    let first_energy = num(&isotope.decay_energy) * 1_000_000.0;
+    let mut without_energy = Vec::new();
+    let decays = [
+        (&isotope.decay_1, &isotope.decay_1_percent),
+        (&isotope.decay_2, &isotope.decay_2_percent),
+        (&isotope.decay_3, &isotope.decay_3_percent),
+    ]
+    .into_iter()
+    .enumerate()
+    .filter_map(|(i, (code, percent))| {
+        let decay_type = DecayType::from_code(code)?;
+        let percent = num(percent);
+        let decay_energy = if i == 0 {
+            first_energy
+        } else if first_energy > 0.0 {
+            q_value(decay_type, isotope.z, isotope.n, mass_excesses).unwrap_or_default()
+        } else {
+            0.0
+        };
+        Some(Decay {
+            decay_type,
+            decay_energy,
+            // without a share the first mode is the only known one
+            intensity: if i == 0 && percent == 0.0 {
+                1.0
+            } else {
+                percent / 100.0
+            },
+        })
+    })
+    .filter(|decay| decay.intensity > 0.0)
+    .filter(|decay| {
+        let needs_energy = !matches!(
+            decay.decay_type,
+            DecayType::SpontaneousFission | DecayType::Other
+        );
+        if first_energy > 0.0 && needs_energy && decay.decay_energy <= 0.0 {
+            without_energy.push(decay.decay_type);
+            return false;
+        }
+        true
+    })
+    .collect();
+    (decays, without_energy)
+}
+
+/// Q value in eV of a decay mode of the isotope with `z` and `n` from the mass excesses in keV,
+/// `None` if a mass excess is missing or the daughter of the mode isn't fixed
+fn q_value(
+    decay_type: DecayType,
+    z: usize,
+    n: usize,
+    mass_excesses: &HashMap<(usize, usize), f64>,
+) -> Option<f32> {
+    let mass_excess = |z: Option<usize>, n: Option<usize>| mass_excesses.get(&(z?, n?)).copied();
+    let parent = mass_excess(Some(z), Some(n))?;
+    let capture = || mass_excess(z.checked_sub(1), Some(n + 1));
+
+    let q = match decay_type {
+        DecayType::Alpha => {
+            parent
+                - mass_excess(z.checked_sub(2), n.checked_sub(2))?
+                - mass_excess(Some(2), Some(2))?
+        }
+        DecayType::BetaMinus => parent - mass_excess(Some(z + 1), n.checked_sub(1))?,
+        DecayType::ElectronCapture | DecayType::BetaElectronCapture => parent - capture()?,
+        // the positron and the surplus electron of the daughter atom
+        DecayType::BetaPlus => {
+            parent - capture()? - 2.0 * bethe::rest_energy(*ELECTRON_MASS) * 1_000.0
+        }
+        DecayType::Protons(count) => {
+            parent
+                - mass_excess(z.checked_sub(count as usize), Some(n))?
+                - count as f64 * mass_excess(Some(1), Some(0))?
+        }
+        DecayType::Neutrons(count) => {
+            parent
+                - mass_excess(Some(z), n.checked_sub(count as usize))?
+                - count as f64 * mass_excess(Some(0), Some(1))?
+        }
+        _ => return None,
+    };
+    (q > 0.0).then_some((q * 1_000.0) as f32)
+}
+
+/// The lines of `photon_lines.csv`, isotopes without lines there
+/// emit the gamma energy of the isotope table with every decay
+fn photon_lines(tables: &TableSet, isotope: &IsotopeDataRow, decays: &[Decay]) -> Vec<PhotonLine> {
+    let main_daughter_z = main_daughter_z(isotope.z, decays);
+    let lines = tables
+        .photon_lines
+        .iter()
+        .filter(|line| line.z == isotope.z && line.n == isotope.n)
+        .map(|line| PhotonLine {
+            photon_type: if line.photon_type == "X" {
+                PhotonType::XRay
+            } else {
+                PhotonType::Gamma
+            },
+            // keV to eV, % to photons per decay
+            energy: num(&line.energy) * 1_000.0,
+            intensity: num(&line.intensity) / 100.0,
+            conversion_coefficient: num(&line.conversion_coefficient),
+            daughter_z: line.daughter_z.unwrap_or(main_daughter_z),
+        })
+        .collect::<Vec<_>>();
+    if !lines.is_empty() {
+        return lines;
+    }
+
+    let gamma_energy = num(&isotope.gamma_energy);
+    if gamma_energy == 0.0 {
+        Vec::new()
+    } else {
+        vec![PhotonLine {
+            photon_type: PhotonType::Gamma,
+            energy: gamma_energy * 1_000_000.0,
+            intensity: 1.0,
+            conversion_coefficient: 0.0,
+            daughter_z: main_daughter_z,
+        }]
+    }
+}
+
+/// Isotopes that can't be used are reported in `errors`
+pub fn get_elements(tables: &TableSet, errors: &mut Vec<DataError>) -> Vec<Arc<Element>> {
+    let element_data = tables.elements.clone();
+    let isotope_data = tables.isotopes.clone();
+
+    let mut alpha_stopping_power = tables.stopping_power_tables(ParticleType::Alpha);
+    let mut electron_stopping_power = tables.stopping_power_tables(ParticleType::Electron);
+    let mut gamma_stopping_power = tables.stopping_power_tables(ParticleType::Gamma);
 
     let activity_constant = *AVOGADRO_CONSTANT * 2f64.log(std::f64::consts::E);
+    // for the Q values of the decay modes without a decay energy
+    let mass_excesses = isotope_data
+        .iter()
+        .filter_map(|isotope| Some(((isotope.z, isotope.n), isotope.mass_excess()?)))
+        .collect::<HashMap<_, _>>();
 
     element_data
         .into_iter()
@@ -76,53 +481,98 @@ pub fn get_elements() -> Vec<Arc<Element>> {
                 .iter()
                 .filter_map(|isotope| {
                     if isotope.z == element.z {
-                        let half_life_raw = parse_num(isotope.half_life_sec.as_str());
+                        let half_life_raw = num(&isotope.half_life_sec);
                         let half_life = if half_life_raw == 0.0 {
                             None
                         } else {
                             Some(half_life_raw)
                         };
 
-                        // convert micro u to u
-                        let atomic_mass = parse_num(&isotope.atomic_mass) / 1_000_000.0;
+                        let atomic_mass = isotope.atomic_mass().unwrap_or_default() as f32;
 
-                        let decay_energy = parse_num(isotope.decay_energy.as_str()) * 1_000_000.0;
+                        let (imported_decays, imported_lines, unassigned) =
+                            decay_radiations(tables, isotope.z, isotope.n);
+                        for electron in unassigned {
+                            errors.push(DataError::file(
+                                DECAY_RADIATION_DIRECTORY,
+                                format!(
+                                    "{}-{} has no gamma line for the {}, it is left out",
+                                    element.symbol,
+                                    isotope.z + isotope.n,
+                                    electron
+                                ),
+                            ));
+                        }
+                        // decay radiation exports replace the columns of the isotope table
+                        let decays = if imported_decays.is_empty() {
+                            for code in [&isotope.decay_1, &isotope.decay_2, &isotope.decay_3] {
+                                if DecayType::from_code(code) == Some(DecayType::Other) {
+                                    errors.push(DataError::file(
+                                        "isotope_data.csv",
+                                        format!(
+                                            "{}-{} has the unknown decay mode \"{}\", it emits nothing",
+                                            element.symbol,
+                                            isotope.z + isotope.n,
+                                            code.trim()
+                                        ),
+                                    ));
+                                }
+                            }
+                            let (decays, without_energy) = table_decays(isotope, &mass_excesses);
+                            for decay_type in without_energy {
+                                errors.push(DataError::file(
+                                    "isotope_data.csv",
+                                    format!(
+                                        "{}-{} has no decay energy for {:?}, the mode is left out",
+                                        element.symbol,
+                                        isotope.z + isotope.n,
+                                        decay_type
+                                    ),
+                                ));
+                            }
+                            decays
+                        } else {
+                            imported_decays
+                        };
+                        let photon_lines = if imported_lines.is_empty() {
+                            photon_lines(tables, isotope, &decays)
+                        } else {
+                            imported_lines
+                        };
+                        let decays_with_energy =
+                            decays.iter().any(|decay| decay.decay_energy > 0.1);
 
                         // calculate Bq/g and conver to Bq/kg
-                        let activity = half_life.map(|half_life| {
-                            ((activity_constant / (half_life * atomic_mass) as f64) * 1_000.0)
-                                as f32
-                        });
+                        let activity = half_life
+                            .map(|half_life| {
+                                ((activity_constant / (half_life * atomic_mass) as f64) * 1_000.0)
+                                    as f32
+                            })
+                            .filter(|activity| activity.is_finite());
+
+                        let is_usable = decays_with_energy && activity.is_some();
+                        if decays_with_energy && activity.is_none() {
+                            errors.push(DataError::file(
+                                "isotope_data.csv",
+                                format!(
+                                    "{}-{} decays but has no half life or atomic mass",
+                                    element.symbol,
+                                    isotope.z + isotope.n
+                                ),
+                            ));
+                        }
 
                         Some(Isotope {
                             z: isotope.z,
                             n: isotope.n,
-                            abundance: ordered_float::OrderedFloat(parse_num(
-                                isotope.abundance.as_str(),
-                            )),
+                            abundance: ordered_float::OrderedFloat(num(&isotope.abundance)),
                             half_life: half_life.map(ordered_float::OrderedFloat),
 
                             atomic_mass,
-                            decays: vec![Decay {
-                                decay_type: match isotope.decay_1.as_str() {
-                                    "B-" => DecayType::BetaMinus,
-                                    "B+" => DecayType::BetaPlus,
-                                    "EC+B+" => DecayType::BetaElectronCapture,
-                                    "A" => DecayType::Alpha,
-                                    _ => DecayType::Other,
-                                },
-                                decay_energy,
-                                gamma_energy: {
-                                    let gamma_energy = parse_num(isotope.gamma_energy.as_str());
-                                    if gamma_energy == 0.0 {
-                                        None
-                                    } else {
-                                        Some(gamma_energy * 1_000_000.0)
-                                    }
-                                },
-                            }],
+                            decays,
+                            photon_lines,
                             activity,
-                            is_usable: decay_energy > 0.1,
+                            is_usable,
                         })
                     } else {
                         None
@@ -138,46 +588,77 @@ pub fn get_elements() -> Vec<Arc<Element>> {
             // stopping powers
             let mut stopping_powers = HashMap::new();
 
-            let is_absorber = alpha_stopping_power.contains_key(&element.z)
-                && electron_stopping_power.contains_key(&element.z)
-                && gamma_stopping_power.contains_key(&element.z);
+            let key = TableKey::Element(element.z);
 
+            // missing tables are calculated, charged particles with the Bethe formula
+            let bethe_material = BetheMaterial {
+                z: element.z as f64,
+                nucleon_ratio: element.nucleon_ratio as f64,
+                mean_excitation_energy: element.energy as f64,
+                density: element.density as f64,
+            };
+            let mut approximated = Vec::new();
+            if !alpha_stopping_power.contains_key(&key) {
+                alpha_stopping_power
+                    .insert(key.clone(), bethe::alpha_stopping_powers(&bethe_material));
+                approximated.push(ParticleType::Alpha);
+            }
+            if !electron_stopping_power.contains_key(&key) {
+                electron_stopping_power.insert(
+                    key.clone(),
+                    bethe::electron_stopping_powers(&bethe_material),
+                );
+                approximated.push(ParticleType::Electron);
+            }
+            if !gamma_stopping_power.contains_key(&key) {
+                gamma_stopping_power.insert(
+                    key.clone(),
+                    photon::mass_attenuation_coefficients(
+                        element.z as f64,
+                        element.nucleon_ratio as f64,
+                    ),
+                );
+                approximated.push(ParticleType::Gamma);
+            }
+            // protons are scaled from the alpha table, neutrons use removal cross sections
+            approximated.extend([ParticleType::Proton, ParticleType::Neutron]);
+
+            // mass stopping powers, the density is applied when they are used
             // 1 cm2/g = 0.1 m2/kg =>
             // 1 MeV*cm2/g = 100_000 eV*m2/kg
-            // 1 eV*m2/kg * 1 kg/m3 = 1 eV/m
-            if let Some(a) = alpha_stopping_power.remove(&element.z) {
-                stopping_powers.insert(
-                    ParticleType::Alpha,
-                    a.into_iter()
-                        .map(|(energy, stop_power)| {
-                            (energy * 1_000_000.0, stop_power * 100_000.0 * density)
-                        })
-                        .collect(),
-                );
+            if let Some(a) = alpha_stopping_power.remove(&key) {
+                let a: StoppingPower = a
+                    .into_iter()
+                    .map(|(energy, stop_power)| (energy * 1_000_000.0, stop_power * 100_000.0))
+                    .collect();
+                stopping_powers.insert(ParticleType::Proton, bethe::proton_stopping_powers(&a));
+                stopping_powers.insert(ParticleType::Alpha, a);
             }
-            if let Some(e) = electron_stopping_power.remove(&element.z) {
+            if let Some(e) = electron_stopping_power.remove(&key) {
                 stopping_powers.insert(
                     ParticleType::Electron,
                     e.into_iter()
-                        .map(|(energy, stop_power)| {
-                            (energy * 1_000_000.0, stop_power * 100_000.0 * density)
-                        })
+                        .map(|(energy, stop_power)| (energy * 1_000_000.0, stop_power * 100_000.0))
                         .collect(),
                 );
             }
 
             // 1 cm2/g = 0.1 m2/kg
-            // 1 m2/kg * 1 kg/m3 = 1/m
-            if let Some(g) = gamma_stopping_power.remove(&element.z) {
+            if let Some(g) = gamma_stopping_power.remove(&key) {
                 stopping_powers.insert(
                     ParticleType::Gamma,
                     g.into_iter()
-                        .map(|(energy, stop_power)| {
-                            (energy * 1_000_000.0, stop_power * 0.1 * density)
-                        })
+                        .map(|(energy, stop_power)| (energy * 1_000_000.0, stop_power * 0.1))
                         .collect(),
                 );
             }
+            stopping_powers.insert(
+                ParticleType::Neutron,
+                neutron::removal_cross_sections(element.z as f64)
+                    .into_iter()
+                    .map(|(energy, cross_section)| (energy * 1_000_000.0, cross_section * 0.1))
+                    .collect(),
+            );
 
             Arc::new(Element {
                 z: element.z,
@@ -188,13 +669,14 @@ pub fn get_elements() -> Vec<Arc<Element>> {
                 density,
                 isotopes,
                 stopping_powers,
-                is_absorber,
+                approximated,
+                is_absorber: true,
             })
         })
         .collect()
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct ElementDataRow {
     pub z: usize,
     pub symbol: String,
@@ -206,17 +688,25 @@ pub struct ElementDataRow {
     pub density: f32,
 }
 
-fn get_element_data() -> Vec<ElementDataRow> {
-    let mut data_reader = csv::Reader::from_reader(Cursor::new(include_bytes!(
-        "./../../assets/simulation_data/element_data.csv"
-    )));
-    data_reader
-        .deserialize()
-        .filter_map(|row| row.ok())
-        .collect()
+impl ValidateRow for ElementDataRow {
+    fn validate(&self) -> Result<(), (&'static str, String)> {
+        if !(0.0..=1.0).contains(&self.nucleon_ratio) || self.nucleon_ratio == 0.0 {
+            return Err((
+                "nucleon_ratio",
+                format!("{} is not in (0, 1]", self.nucleon_ratio),
+            ));
+        }
+        if self.energy.is_nan() || self.energy <= 0.0 {
+            return Err(("energy", format!("{} eV is not positive", self.energy)));
+        }
+        if !self.density.is_finite() || self.density < 0.0 {
+            return Err(("density", format!("{} g/cm3 is not valid", self.density)));
+        }
+        Ok(())
+    }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
 pub struct IsotopeDataRow {
     pub z: usize,
     pub n: usize,
@@ -240,130 +730,324 @@ pub struct IsotopeDataRow {
     pub gamma_energy: String,
     /// in micro u
     pub atomic_mass: String,
+    /// in keV or eV, see `mass_excess`
     pub massexcess: String,
 }
 
-fn get_isotope_data() -> Vec<IsotopeDataRow> {
-    let mut data_reader = csv::Reader::from_reader(Cursor::new(include_bytes!(
-        "./../../assets/simulation_data/isotope_data.csv"
-    )));
-    data_reader
-        .deserialize()
-        .filter_map(|row| row.ok())
-        .collect()
+/// 1 u in keV
+const ATOMIC_MASS_UNIT_ENERGY: f64 = 931_494.1;
+
+impl IsotopeDataRow {
+    /// in u, rows of some sources are in nano u instead of micro u
+    pub fn atomic_mass(&self) -> Option<f64> {
+        let atomic_mass = self.atomic_mass.trim().parse::<f64>().ok()? / 1_000_000.0;
+        let mass_number = (self.z + self.n).max(1) as f64;
+        Some(if atomic_mass > 10.0 * mass_number {
+            atomic_mass / 1_000.0
+        } else {
+            atomic_mass
+        })
+    }
+
+    /// in keV, rows of some sources are in eV. The unit that matches the mass excess
+    /// calculated from the atomic mass is taken.
+    pub fn mass_excess(&self) -> Option<f64> {
+        let mass_excess = self.massexcess.trim().parse::<f64>().ok()?;
+        let from_atomic_mass =
+            (self.atomic_mass()? - (self.z + self.n) as f64) * ATOMIC_MASS_UNIT_ENERGY;
+        Some(
+            if (mass_excess - from_atomic_mass).abs()
+                <= (mass_excess / 1_000.0 - from_atomic_mass).abs()
+            {
+                mass_excess
+            } else {
+                mass_excess / 1_000.0
+            },
+        )
+    }
 }
 
-// technically this is a mass attenuation coeffients but data reading and storing is similar
-fn get_stopping_power(particle_type: ParticleType) -> HashMap<usize, Vec<(f32, f32)>> {
-    #[rustfmt::skip]
-    let table_data = match particle_type {
-        ParticleType::Alpha => {vec![
-            (1_usize, include_str!("./../../assets/simulation_data/stopping_power_alpha/01.csv")),
-            (2, include_str!("./../../assets/simulation_data/stopping_power_alpha/02.csv")),
-            (4, include_str!("./../../assets/simulation_data/stopping_power_alpha/04.csv")),
-            (6, include_str!("./../../assets/simulation_data/stopping_power_alpha/06.csv")),
-            (7, include_str!("./../../assets/simulation_data/stopping_power_alpha/07.csv")),
-            (8, include_str!("./../../assets/simulation_data/stopping_power_alpha/08.csv")),
-            (10, include_str!("./../../assets/simulation_data/stopping_power_alpha/10.csv")),
-            (13, include_str!("./../../assets/simulation_data/stopping_power_alpha/13.csv")),
-            (14, include_str!("./../../assets/simulation_data/stopping_power_alpha/14.csv")),
-            (18, include_str!("./../../assets/simulation_data/stopping_power_alpha/18.csv")),
-            (22, include_str!("./../../assets/simulation_data/stopping_power_alpha/22.csv")),
-            (26, include_str!("./../../assets/simulation_data/stopping_power_alpha/26.csv")),
-            (29, include_str!("./../../assets/simulation_data/stopping_power_alpha/29.csv")),
-            (32, include_str!("./../../assets/simulation_data/stopping_power_alpha/32.csv")),
-            (82, include_str!("./../../assets/simulation_data/stopping_power_alpha/82.csv")),
-        ]}
-        ParticleType::Electron => {vec![
-            (1_usize, include_str!("./../../assets/simulation_data/stopping_power_electrons/01.csv")),
-            (2, include_str!("./../../assets/simulation_data/stopping_power_electrons/02.csv")),
-            (3, include_str!("./../../assets/simulation_data/stopping_power_electrons/03.csv")),
-            (4, include_str!("./../../assets/simulation_data/stopping_power_electrons/04.csv")),
-            (5, include_str!("./../../assets/simulation_data/stopping_power_electrons/05.csv")),
-            (6, include_str!("./../../assets/simulation_data/stopping_power_electrons/06.csv")),
-            (7, include_str!("./../../assets/simulation_data/stopping_power_electrons/07.csv")),
-            (8, include_str!("./../../assets/simulation_data/stopping_power_electrons/08.csv")),
-            (9, include_str!("./../../assets/simulation_data/stopping_power_electrons/09.csv")),
-            (10, include_str!("./../../assets/simulation_data/stopping_power_electrons/10.csv")),
-            (11, include_str!("./../../assets/simulation_data/stopping_power_electrons/11.csv")),
-            (12, include_str!("./../../assets/simulation_data/stopping_power_electrons/12.csv")),
-            (13, include_str!("./../../assets/simulation_data/stopping_power_electrons/13.csv")),
-            (14, include_str!("./../../assets/simulation_data/stopping_power_electrons/14.csv")),
-            (82, include_str!("./../../assets/simulation_data/stopping_power_electrons/82.csv")),
-        ]}
-        _ => panic!("requested stopping power table for not registered particle"),
-    };
+impl ValidateRow for IsotopeDataRow {
+    fn validate(&self) -> Result<(), (&'static str, String)> {
+        optional_num(&self.abundance, "abundance")?;
+        optional_num(&self.half_life_sec, "half_life_sec")?;
+        optional_num(&self.decay_1_percent, "decay_1_%")?;
+        optional_num(&self.decay_2_percent, "decay_2_%")?;
+        optional_num(&self.decay_3_percent, "decay_3_%")?;
+        optional_num(&self.decay_energy, "decay_energy")?;
+        optional_num(&self.gamma_energy, "gamma_energy")?;
+        optional_num(&self.atomic_mass, "atomic_mass")?;
+        Ok(())
+    }
+}
 
-    let mut stopping_powers = HashMap::new();
-
-    for (z, data) in table_data {
-        let mut data_reader = csv::Reader::from_reader(Cursor::new(data));
-        stopping_powers.insert(
-            z,
-            data_reader
-                .deserialize()
-                .filter_map(|row| {
-                    row.map_err(|e| {
-                        log::warn!("Error reading row ({}, a/e): {}", z, e);
-                        e
-                    })
-                    .ok()
-                })
-                .map(|row: StoppingPowerRow| {
-                    (
-                        parse_num(row.energy.as_str()),
-                        parse_num(row.stop_power.as_str()),
-                    )
-                })
-                .collect(),
-        );
+/// A row of `photon_lines.csv`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct PhotonLineRow {
+    pub z: usize,
+    pub n: usize,
+    /// `G` for gamma rays, `X` for X-rays
+    #[serde(rename = "type")]
+    pub photon_type: String,
+    /// in keV
+    pub energy: String,
+    /// in % of the decays
+    pub intensity: String,
+    /// conversion electrons per photon of the line, blank if unknown
+    #[serde(default)]
+    pub conversion_coefficient: String,
+    /// atomic number of the atom emitting the line,
+    /// blank for the daughter of the most frequent decay branch
+    #[serde(default)]
+    pub daughter_z: Option<usize>,
+}
+
+impl ValidateRow for PhotonLineRow {
+    fn validate(&self) -> Result<(), (&'static str, String)> {
+        if !["G", "X"].contains(&self.photon_type.as_str()) {
+            return Err((
+                "type",
+                format!("unknown type {}, expected G or X", self.photon_type),
+            ));
+        }
+        let energy = require_num(&self.energy, "energy")?;
+        if energy.is_nan() || energy <= 0.0 {
+            return Err(("energy", format!("{} keV is not positive", energy)));
+        }
+        let intensity = require_num(&self.intensity, "intensity")?;
+        if !intensity.is_finite() || intensity < 0.0 {
+            return Err(("intensity", format!("{} % is not valid", intensity)));
+        }
+        let conversion_coefficient =
+            optional_num(&self.conversion_coefficient, "conversion_coefficient")?;
+        if !conversion_coefficient.is_finite() || conversion_coefficient < 0.0 {
+            return Err((
+                "conversion_coefficient",
+                format!("{} is not valid", conversion_coefficient),
+            ));
+        }
+        Ok(())
     }
+}
 
-    stopping_powers
+/// Radiation types of the IAEA LiveChart exports
+pub const RADIATION_TYPES: [&str; 6] = ["a", "bm", "bp", "g", "x", "e"];
+
+/// A row of an IAEA LiveChart (ENSDF) decay radiation export in `decay_radiations/`,
+/// other columns of the export are ignored. The radiation type is taken from
+/// the `rad_type` column or the start of the file name, like `g` in `g_cs137.csv`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct DecayRadiationRow {
+    /// parent nuclide
+    pub p_z: usize,
+    pub p_n: usize,
+    #[serde(default)]
+    pub rad_type: String,
+    /// in keV
+    #[serde(default)]
+    pub energy: String,
+    /// in keV, the maximum energy of beta spectra
+    #[serde(default)]
+    pub endpoint_energy: String,
+    /// in % of the decays of the parent
+    pub intensity: String,
+    /// kind of electron, like `CE K` or `Auger L`
+    #[serde(default, rename = "type")]
+    pub electron_type: String,
+    /// daughter nuclide, the atom emitting gamma and X-rays
+    #[serde(default)]
+    pub d_z: Option<usize>,
 }
 
-// technically this is a mass attenuation coeffients but data reading and storing is similar
-fn get_gamma_stopping_power() -> HashMap<usize, Vec<(f32, f32)>> {
-    #[rustfmt::skip]
-    let table_data = vec![
-        (1_usize, include_str!("./../../assets/simulation_data/mass_attenuation_coefficients/01.csv")),
-        (2, include_str!("./../../assets/simulation_data/mass_attenuation_coefficients/02.csv")),
-        (3, include_str!("./../../assets/simulation_data/mass_attenuation_coefficients/03.csv")),
-        (4, include_str!("./../../assets/simulation_data/mass_attenuation_coefficients/04.csv")),
-        (5, include_str!("./../../assets/simulation_data/mass_attenuation_coefficients/05.csv")),
-        (6, include_str!("./../../assets/simulation_data/mass_attenuation_coefficients/06.csv")),
-        (7, include_str!("./../../assets/simulation_data/mass_attenuation_coefficients/07.csv")),
-        (8, include_str!("./../../assets/simulation_data/mass_attenuation_coefficients/08.csv")),
-        (9, include_str!("./../../assets/simulation_data/mass_attenuation_coefficients/09.csv")),
-        (10, include_str!("./../../assets/simulation_data/mass_attenuation_coefficients/10.csv")),
-        (11, include_str!("./../../assets/simulation_data/mass_attenuation_coefficients/11.csv")),
-        (12, include_str!("./../../assets/simulation_data/mass_attenuation_coefficients/12.csv")),
-        (13, include_str!("./../../assets/simulation_data/mass_attenuation_coefficients/13.csv")),
-        (14, include_str!("./../../assets/simulation_data/mass_attenuation_coefficients/14.csv")),
-        (82, include_str!("./../../assets/simulation_data/mass_attenuation_coefficients/82.csv")),
-    ];
-
-    let mut stopping_powers = HashMap::new();
-
-    for (z, data) in table_data {
-        let mut data_reader = csv::Reader::from_reader(Cursor::new(data));
-        stopping_powers.insert(
-            z,
-            data_reader
-                .deserialize()
-                .filter_map(|row| {
-                    row.map_err(|e| {
-                        log::warn!("Error reading row({}, g): {}", z, e);
-                        e
-                    })
-                    .ok()
-                })
-                .map(|row: MassAttenuationCoefficientRow| {
-                    (parse_num(row.energy.as_str()), parse_num(row.yp.as_str()))
-                })
-                .collect(),
+impl DecayRadiationRow {
+    /// in keV, betas use their endpoint energy
+    pub fn energy(&self) -> &str {
+        if self.endpoint_energy.trim().is_empty() {
+            &self.energy
+        } else {
+            &self.endpoint_energy
+        }
+    }
+
+    /// Conversion electrons are `CE` electrons of the `e` type, electrons without
+    /// a kind are taken as conversion electrons as well
+    pub fn is_conversion_electron(&self) -> bool {
+        let electron_type = self.electron_type.trim();
+        self.rad_type == "e" && (electron_type.is_empty() || electron_type.starts_with("CE"))
+    }
+
+    /// The shell of a conversion electron, like `K` for `CE K`
+    pub fn shell(&self) -> Option<char> {
+        self.electron_type
+            .trim()
+            .strip_prefix("CE")?
+            .trim()
+            .chars()
+            .next()
+    }
+}
+
+impl ValidateRow for DecayRadiationRow {
+    fn validate(&self) -> Result<(), (&'static str, String)> {
+        if !self.rad_type.is_empty() && !RADIATION_TYPES.contains(&self.rad_type.as_str()) {
+            return Err(("rad_type", format!("unknown type {}", self.rad_type)));
+        }
+        let energy = require_num(self.energy(), "energy")?;
+        if energy.is_nan() || energy <= 0.0 {
+            return Err(("energy", format!("{} keV is not positive", energy)));
+        }
+        let intensity = require_num(&self.intensity, "intensity")?;
+        if !intensity.is_finite() || intensity < 0.0 {
+            return Err(("intensity", format!("{} % is not valid", intensity)));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decay_codes() {
+        assert_eq!(DecayType::from_code(""), None);
+        assert_eq!(DecayType::from_code("B-"), Some(DecayType::BetaMinus));
+        assert_eq!(
+            DecayType::from_code("EC+B+"),
+            Some(DecayType::BetaElectronCapture)
+        );
+        assert_eq!(DecayType::from_code(" A "), Some(DecayType::Alpha));
+        assert_eq!(
+            DecayType::from_code("IT"),
+            Some(DecayType::IsomericTransition)
+        );
+    }
+
+    #[test]
+    fn delayed_particles() {
+        assert_eq!(
+            DecayType::from_code("B-N"),
+            Some(DecayType::BetaDelayedNeutrons(1))
+        );
+        assert_eq!(
+            DecayType::from_code("B-2N"),
+            Some(DecayType::BetaDelayedNeutrons(2))
+        );
+        assert_eq!(
+            DecayType::from_code("ECP"),
+            Some(DecayType::BetaDelayedProtons(1))
+        );
+        assert_eq!(
+            DecayType::from_code("ECP+EC2P"),
+            Some(DecayType::BetaDelayedProtons(1))
         );
+        assert_eq!(DecayType::from_code("2P"), Some(DecayType::Protons(2)));
     }
 
-    stopping_powers
+    #[test]
+    fn fission_and_clusters() {
+        assert_eq!(
+            DecayType::from_code("SF"),
+            Some(DecayType::SpontaneousFission)
+        );
+        assert_eq!(
+            DecayType::from_code("SF+EC+B+"),
+            Some(DecayType::SpontaneousFission)
+        );
+        assert_eq!(
+            DecayType::from_code("24NE"),
+            Some(DecayType::ClusterEmission)
+        );
+        assert_eq!(DecayType::from_code("B-?"), Some(DecayType::Other));
+    }
+
+    #[test]
+    fn secondary_mode_energies() {
+        // Bi-212 decays by B- with the tabulated energy and by alpha
+        let isotope = IsotopeDataRow {
+            z: 83,
+            n: 129,
+            decay_1: "B-".to_owned(),
+            decay_1_percent: "64.06".to_owned(),
+            decay_2: "A".to_owned(),
+            decay_2_percent: "35.94".to_owned(),
+            decay_3: "B-N".to_owned(),
+            decay_3_percent: "0.01".to_owned(),
+            decay_energy: "2.252".to_owned(),
+            ..Default::default()
+        };
+        let mass_excesses = [
+            ((83, 129), -8117.2),
+            ((81, 127), -16749.5),
+            ((2, 2), 2424.9),
+        ]
+        .into_iter()
+        .collect();
+
+        let (decays, without_energy) = table_decays(&isotope, &mass_excesses);
+        assert_eq!(decays.len(), 2);
+        assert_eq!(decays[0].decay_type, DecayType::BetaMinus);
+        assert!((decays[0].decay_energy - 2.252e6).abs() < 1.0);
+        assert_eq!(decays[1].decay_type, DecayType::Alpha);
+        assert!((decays[1].decay_energy - 6.2074e6).abs() < 100.0);
+        // the daughter of beta delayed neutrons isn't fixed
+        assert_eq!(without_energy, vec![DecayType::BetaDelayedNeutrons(1)]);
+    }
+
+    #[test]
+    fn mass_excess_units() {
+        let row = |atomic_mass: &str, massexcess: &str| IsotopeDataRow {
+            z: 55,
+            n: 82,
+            atomic_mass: atomic_mass.to_owned(),
+            massexcess: massexcess.to_owned(),
+            ..Default::default()
+        };
+        // Cs-137 in micro u and keV, and in nano u and eV
+        let micro_u = row("136907089.3", "-86545.8");
+        let nano_u = row("136907089296", "-86545772");
+        assert!((micro_u.atomic_mass().unwrap() - 136.907).abs() < 0.001);
+        assert!((nano_u.atomic_mass().unwrap() - 136.907).abs() < 0.001);
+        assert!((micro_u.mass_excess().unwrap() + 86545.8).abs() < 0.1);
+        assert!((nano_u.mass_excess().unwrap() + 86545.8).abs() < 0.1);
+    }
+
+    #[test]
+    fn conversion_electrons() {
+        use super::super::tables::{DataAsset, DataFile};
+
+        // Ba-137m from Cs-137, with an Auger electron and a conversion line without gamma
+        let gammas = "\
+energy,unc_en,intensity,unc_i,p_z,p_n,p_symbol,d_z,d_n,d_symbol
+661.657,0.003,85.1,0.2,55,82,Cs,56,81,Ba
+";
+        let electrons = "\
+energy,unc_en,intensity,unc_i,type,p_z,p_n,p_symbol,d_z,d_n,d_symbol
+624.216,0.003,7.79,0.04,CE K,55,82,Cs,56,81,Ba
+655.668,0.003,1.402,0.008,CE L,55,82,Cs,56,81,Ba
+26.4,,0.8,0.2,Auger K,55,82,Cs,56,81,Ba
+100.0,,0.5,,CE K,55,82,Cs,56,81,Ba
+";
+        let mut tables = TableSet::default();
+        for (path, data) in [
+            ("decay_radiations/g_cs137.csv", gammas),
+            ("decay_radiations/e_cs137.csv", electrons),
+        ] {
+            match DataFile::parse(path.as_ref(), data.as_bytes()) {
+                Some(DataFile::DecayRadiations(table)) => {
+                    assert!(table.errors.is_empty(), "{:?}", table.errors);
+                    table.apply(&mut tables);
+                }
+                _ => panic!("{} is no decay radiation export", path),
+            }
+        }
+
+        let (decays, lines, unassigned) = decay_radiations(&tables, 55, 82);
+        assert!(decays.is_empty());
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].daughter_z, 56);
+        let coefficient = (7.79 + 1.402) / 85.1;
+        assert!((lines[0].conversion_coefficient - coefficient).abs() < 1e-4);
+        assert_eq!(unassigned.len(), 1);
+    }
 }
@@ -0,0 +1,81 @@
+use bevy::prelude::*;
+
+/// A problem in a data file
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataError {
+    /// path relative to the data directory
+    pub file: String,
+    /// line in the file, `None` for problems with the whole file
+    pub line: Option<u64>,
+    pub column: Option<String>,
+    pub reason: String,
+}
+
+impl DataError {
+    pub fn file(file: &str, reason: impl Into<String>) -> Self {
+        Self {
+            file: file.to_owned(),
+            line: None,
+            column: None,
+            reason: reason.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for DataError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.file)?;
+        if let Some(line) = self.line {
+            f.write_fmt(format_args!(":{}", line))?;
+        }
+        if let Some(column) = &self.column {
+            f.write_fmt(format_args!(" ({})", column))?;
+        }
+        f.write_fmt(format_args!(": {}", self.reason))
+    }
+}
+
+impl std::error::Error for DataError {}
+
+/// All problems found while reading the data
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DataErrors(pub Vec<DataError>);
+
+impl std::fmt::Display for DataErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "{} errors in the simulation data",
+            self.0.len()
+        ))?;
+        for error in &self.0 {
+            f.write_fmt(format_args!("\n{}", error))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for DataErrors {}
+
+/// How errors in the data files are handled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Resource)]
+pub enum DataLoadingMode {
+    /// any error stops the startup
+    Strict,
+    /// rows with errors are skipped and listed as warnings in the UI
+    #[default]
+    Lenient,
+}
+
+impl DataLoadingMode {
+    /// Command line flag that turns on strict mode
+    pub const STRICT_FLAG: &'static str = "--strict-data";
+
+    /// Strict if the program was started with `--strict-data`
+    pub fn from_args() -> Self {
+        if std::env::args().any(|arg| arg == Self::STRICT_FLAG) {
+            Self::Strict
+        } else {
+            Self::Lenient
+        }
+    }
+}
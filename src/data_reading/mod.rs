@@ -6,45 +6,58 @@ use serde::Deserialize;
 
 use crate::ParticleType;
 
+pub mod bethe;
 pub mod compound;
 pub mod element;
+pub mod error;
+pub mod neutron;
+pub mod photon;
+pub mod relaxation;
+pub mod tables;
 
-pub use compound::Compound;
-pub use element::Element;
+pub use compound::{Compound, CompoundError, Constituent};
+pub use element::{Element, Isotope};
+pub use error::{DataError, DataErrors, DataLoadingMode};
+pub use tables::{TableKey, TableSet};
 
-/// (energy in eV, stopping power in eV/m | 1/m)
+/// (energy in eV, mass stopping power in eV*m2/kg | mass attenuation coefficient in m2/kg
+/// for gammas | mass removal cross section in m2/kg for neutrons)
 pub type StoppingPower = Vec<(f32, f32)>;
 
 #[derive(Debug, Clone, Reflect, FromReflect)]
 pub enum Substance {
     Element(#[reflect(ignore)] Arc<Element>, usize),
+    /// element with the isotope mix found in nature
+    NaturalElement(#[reflect(ignore)] Arc<Element>),
     Compound(#[reflect(ignore)] Arc<Compound>),
 }
 
 impl Substance {
     pub fn symbol(&self) -> &String {
         match &self {
-            Substance::Element(e, _) => &e.symbol,
+            Substance::Element(e, _) | Substance::NaturalElement(e) => &e.symbol,
             Substance::Compound(c) => &c.symbol,
         }
     }
     pub fn name(&self) -> &String {
         match &self {
-            Substance::Element(e, _) => &e.name,
+            Substance::Element(e, _) | Substance::NaturalElement(e) => &e.name,
             Substance::Compound(c) => &c.name,
         }
     }
     /// in kg/m3
     pub fn density(&self) -> f32 {
         match &self {
-            Substance::Element(e, _) => e.density,
+            Substance::Element(e, _) | Substance::NaturalElement(e) => e.density,
             Substance::Compound(c) => c.density,
         }
     }
 
     pub fn stopping_powers(&self, particle_type: ParticleType) -> Option<&StoppingPower> {
         match &self {
-            Substance::Element(e, _) => e.stopping_powers.get(&particle_type),
+            Substance::Element(e, _) | Substance::NaturalElement(e) => {
+                e.stopping_powers.get(&particle_type)
+            }
             Substance::Compound(c) => c.stopping_powers.get(&particle_type),
         }
     }
@@ -52,23 +65,67 @@ impl Substance {
     /// if all required info is available for it to absorb radiation
     pub fn is_absorber(&self) -> bool {
         match &self {
-            Substance::Element(e, _) => e.is_absorber,
+            Substance::Element(e, _) | Substance::NaturalElement(e) => e.is_absorber,
             Substance::Compound(c) => c.is_absorber,
         }
     }
+
+    /// particles whose stopping powers or attenuation coefficients were calculated
+    pub fn approximated(&self) -> &[ParticleType] {
+        match &self {
+            Substance::Element(e, _) | Substance::NaturalElement(e) => &e.approximated,
+            Substance::Compound(c) => &c.approximated,
+        }
+    }
+
+    /// (z, mass fraction) of the elements, empty for compounds with tabulated data
+    pub fn elements(&self) -> Vec<(usize, f32)> {
+        match &self {
+            Substance::Element(e, _) | Substance::NaturalElement(e) => vec![(e.z, 1.0)],
+            Substance::Compound(c) => c.composition.clone(),
+        }
+    }
+
+    /// The isotopes that can decay with their activity in Bq per kg of the substance
+    pub fn radioactive_isotopes(&self) -> Vec<(&Isotope, f32)> {
+        match &self {
+            Substance::Element(e, n) => e
+                .isotopes
+                .get(n)
+                .filter(|isotope| isotope.is_usable)
+                .and_then(|isotope| isotope.activity.map(|activity| (isotope, activity)))
+                .into_iter()
+                .collect(),
+            Substance::NaturalElement(e) => e
+                .natural_isotopes()
+                .into_iter()
+                .filter(|(isotope, _)| isotope.is_usable)
+                .filter_map(|(isotope, mass_fraction)| {
+                    isotope
+                        .activity
+                        .map(|activity| (isotope, activity * mass_fraction))
+                })
+                .collect(),
+            Substance::Compound(c) => c
+                .radioactive_isotopes
+                .iter()
+                .map(|(isotope, activity)| (isotope, *activity))
+                .collect(),
+        }
+    }
 }
 
 impl PartialEq for Substance {
     fn eq(&self, other: &Self) -> bool {
-        match self {
-            Self::Element(arc, n) => match other {
-                Self::Element(arc_other, n_other) => Arc::ptr_eq(arc, arc_other) && n == n_other,
-                Self::Compound(_) => false,
-            },
-            Self::Compound(arc) => match other {
-                Self::Element(_, _) => false,
-                Self::Compound(arc_other) => Arc::ptr_eq(arc, arc_other),
-            },
+        match (self, other) {
+            (Self::Element(arc, n), Self::Element(arc_other, n_other)) => {
+                Arc::ptr_eq(arc, arc_other) && n == n_other
+            }
+            (Self::NaturalElement(arc), Self::NaturalElement(arc_other)) => {
+                Arc::ptr_eq(arc, arc_other)
+            }
+            (Self::Compound(arc), Self::Compound(arc_other)) => Arc::ptr_eq(arc, arc_other),
+            _ => false,
         }
     }
 }
@@ -77,6 +134,7 @@ impl std::fmt::Display for Substance {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Element(e, n) => f.write_fmt(format_args!("{} {}", e.name, e.z + n)),
+            Self::NaturalElement(e) => f.write_fmt(format_args!("{} nat.", e.name)),
             Self::Compound(c) => f.write_str(&c.name),
         }
     }
@@ -96,6 +154,10 @@ impl Default for Substance {
                     name: "Vakuum".to_owned(),
                     density: 0.0,
                     stopping_powers,
+                    composition: Vec::new(),
+                    constituents: Vec::new(),
+                    radioactive_isotopes: Vec::new(),
+                    approximated: Vec::new(),
                     is_absorber: true,
                 })
             };
@@ -123,15 +185,22 @@ pub struct MassAttenuationCoefficientRow {
     pub yenp: String,
 }
 
-/// Parse numbers with scientific notation.
-/// Will never fail, just return 0.
-fn parse_num(num: &str) -> f32 {
+/// Parse numbers with scientific notation, empty fields are an error
+pub fn parse_num(num: &str) -> Result<f32, String> {
+    let num = num.trim();
+    if num.is_empty() {
+        return Err("missing value".to_owned());
+    }
+    let invalid = || format!("invalid number \"{}\"", num);
+
     // scientific notation
-    if !num.contains('E') {
-        num.parse().unwrap_or(0.0)
-    } else {
-        let num = num.split_once('E').unwrap();
-        num.0.parse().unwrap_or(0.0) * 10_f32.powi(num.1.parse().unwrap_or(0))
+    match num.split_once('E') {
+        None => num.parse().map_err(|_| invalid()),
+        Some((mantissa, exponent)) => {
+            let mantissa: f32 = mantissa.parse().map_err(|_| invalid())?;
+            let exponent: i32 = exponent.parse().map_err(|_| invalid())?;
+            Ok(mantissa * 10_f32.powi(exponent))
+        }
     }
 }
 
@@ -139,13 +208,38 @@ pub struct RadiationSimData;
 
 impl Plugin for RadiationSimData {
     fn build(&self, app: &mut App) {
-        app.insert_resource(SubstanceData {
-            elements: BTreeMap::new(),
-            compounds: BTreeMap::new(),
-            radiators: Vec::new(),
-            absorbers: Vec::new(),
-        })
-        .add_startup_system(read_data.in_base_set(StartupSet::PreStartup));
+        app.add_asset::<tables::ElementTable>()
+            .add_asset::<tables::IsotopeTable>()
+            .add_asset::<tables::CompoundTable>()
+            .add_asset::<tables::PhotonLineTable>()
+            .add_asset::<tables::DecayRadiationTable>()
+            .add_asset::<tables::StoppingPowerTable>()
+            .init_asset_loader::<tables::DataFileLoader>()
+            .init_resource::<tables::DataHandles>()
+            .add_event::<SubstanceDataReloaded>()
+            .init_resource::<DataLoadingMode>()
+            .insert_resource(TableSet::load())
+            .insert_resource(SubstanceData {
+                elements: BTreeMap::new(),
+                compounds: BTreeMap::new(),
+                radiators: Vec::new(),
+                absorbers: Vec::new(),
+                warnings: Vec::new(),
+            })
+            .add_startup_system(read_data.in_base_set(StartupSet::PreStartup))
+            .add_startup_system(tables::load_data_files)
+            .add_systems(
+                (
+                    tables::apply_data_files::<tables::ElementTable>,
+                    tables::apply_data_files::<tables::IsotopeTable>,
+                    tables::apply_data_files::<tables::CompoundTable>,
+                    tables::apply_data_files::<tables::PhotonLineTable>,
+                    tables::apply_data_files::<tables::DecayRadiationTable>,
+                    tables::apply_data_files::<tables::StoppingPowerTable>,
+                )
+                    .before(rebuild_substance_data),
+            )
+            .add_system(rebuild_substance_data);
     }
 }
 
@@ -155,81 +249,261 @@ pub struct SubstanceData {
     pub compounds: BTreeMap<String, Arc<Compound>>,
     pub radiators: Vec<Substance>,
     pub absorbers: Vec<Substance>,
+    /// problems in the data files, skipped rows and unusable entries
+    pub warnings: Vec<DataError>,
 }
 
-pub fn read_data(mut substance_data: ResMut<SubstanceData>) {
-    // elements
-    let element_data = element::get_elements();
-    let mut element_btree = BTreeMap::new();
-    for element in element_data {
-        element_btree.insert(element.z, element);
-    }
-    substance_data.elements = element_btree;
-
-    let mut radiators = Vec::new();
-    let mut absorbers = Vec::new();
-    for (_, element) in &substance_data.elements {
-        for (n, isotope) in &element.isotopes {
-            if isotope.is_usable {
-                radiators.push(Substance::Element(element.clone(), *n));
-            }
+impl SubstanceData {
+    /// find an absorber by compound name, element symbol or element name
+    pub fn find_absorber(&self, name: &str) -> Option<Substance> {
+        self.absorbers
+            .iter()
+            .find(|substance| substance.name() == name || substance.symbol() == name)
+            .cloned()
+    }
+
+    pub fn element_by_symbol(&self, symbol: &str) -> Option<&Arc<Element>> {
+        self.elements
+            .values()
+            .find(|element| element.symbol == symbol)
+    }
+
+    /// Add a compound built from a chemical formula, density in kg/m3
+    pub fn add_compound_from_formula(
+        &mut self,
+        name: &str,
+        formula: &str,
+        density: f32,
+    ) -> Result<Arc<Compound>, CompoundError> {
+        let compound = Compound::from_formula(name, formula, density, &self.elements)?;
+        self.add_compound(compound)
+    }
+
+    /// Add a compound built from its constituents, density in kg/m3
+    pub fn add_compound_from_mass_fractions(
+        &mut self,
+        name: &str,
+        mass_fractions: &[Constituent],
+        density: f32,
+    ) -> Result<Arc<Compound>, CompoundError> {
+        let compound =
+            Compound::from_mass_fractions(name, name, mass_fractions, density, &self.elements)?;
+        self.add_compound(compound)
+    }
+
+    pub fn add_compound(&mut self, compound: Compound) -> Result<Arc<Compound>, CompoundError> {
+        if self.compounds.contains_key(&compound.name) {
+            return Err(CompoundError::DuplicateName(compound.name));
         }
-        if element.is_absorber {
-            let mut isotopes_sorted = element.isotopes.clone().into_values().collect::<Vec<_>>();
-            isotopes_sorted.sort_by_key(|i| i.abundance);
-            absorbers.push(Substance::Element(
-                element.clone(),
-                isotopes_sorted.last().unwrap().n,
-            ));
+
+        let compound = Arc::new(compound);
+        self.compounds
+            .insert(compound.name.clone(), compound.clone());
+        if compound.is_absorber {
+            self.absorbers.push(Substance::Compound(compound.clone()));
         }
+        Ok(compound)
     }
-    substance_data.radiators = radiators;
-    substance_data.absorbers = absorbers;
 
-    // compounds
-    let compound_data = compound::get_compounds();
-    let mut compound_btree = BTreeMap::new();
-    for compound in compound_data {
-        compound_btree.insert(compound.name.to_owned(), compound);
+    /// The substance with the same element and isotope or compound name
+    pub fn find_same(&self, substance: &Substance) -> Option<Substance> {
+        match substance {
+            Substance::Element(element, n) => self
+                .elements
+                .get(&element.z)
+                .map(|element| Substance::Element(element.clone(), *n)),
+            Substance::NaturalElement(element) => self
+                .elements
+                .get(&element.z)
+                .map(|element| Substance::NaturalElement(element.clone())),
+            Substance::Compound(compound) => self
+                .compounds
+                .get(&compound.name)
+                .map(|compound| Substance::Compound(compound.clone())),
+        }
     }
-    substance_data.compounds = compound_btree;
 
-    let mut absorbers = Vec::new();
-    for (_, compound) in &substance_data.compounds {
-        if compound.is_absorber {
-            absorbers.push(Substance::Compound(compound.clone()));
-        }
-    }
-    substance_data.absorbers.extend(absorbers);
-
-    // nice logs
-    for e in &substance_data.radiators {
-        match &e {
-            Substance::Element(element, n) => {
-                let isotope = &element.isotopes[n];
-                log::info!(
-                    "{} {:?}: {:?} eV, {:?} ev, {} Bq/kg",
-                    element.symbol,
-                    element.z + n,
-                    isotope.decays[0].decay_energy,
-                    isotope.decays[0].gamma_energy,
-                    isotope.activity.unwrap()
-                );
-            }
-            Substance::Compound(compound) => {
-                log::info!("{}", &compound.name);
+    /// Rebuild from the tables, compounds created at runtime are kept.
+    /// On errors in strict mode the current data is kept.
+    pub fn rebuild(&mut self, tables: &TableSet, mode: DataLoadingMode) -> Result<(), DataErrors> {
+        let created_compounds = self
+            .compounds
+            .values()
+            .filter(|compound| !compound.composition.is_empty())
+            .cloned()
+            .collect::<Vec<_>>();
+
+        *self = Self::load(tables, mode)?;
+
+        for compound in created_compounds {
+            let result = Compound::from_mass_fractions(
+                &compound.name,
+                &compound.symbol,
+                &compound.constituents,
+                compound.density,
+                &self.elements,
+            )
+            .and_then(|rebuilt| self.add_compound(rebuilt));
+            if let Err(e) = result {
+                log::warn!("Can't rebuild compound {}: {}", compound.name, e);
             }
         }
+        Ok(())
+    }
+
+    /// Fails in strict mode if there is any problem with the data
+    pub fn load(tables: &TableSet, mode: DataLoadingMode) -> Result<Self, DataErrors> {
+        let substance_data = Self::from_tables(tables);
+        if mode == DataLoadingMode::Strict && !substance_data.warnings.is_empty() {
+            return Err(DataErrors(substance_data.warnings));
+        }
+        for warning in &substance_data.warnings {
+            log::warn!("{}", warning);
+        }
+        Ok(substance_data)
     }
 
-    for e in &substance_data.absorbers {
-        match &e {
-            Substance::Element(element, _) => {
-                log::info!("{} Absorber", element.symbol);
+    /// Skips everything with errors, they are collected in `warnings`
+    pub fn from_tables(tables: &TableSet) -> Self {
+        let mut substance_data = SubstanceData {
+            elements: BTreeMap::new(),
+            compounds: BTreeMap::new(),
+            radiators: Vec::new(),
+            absorbers: Vec::new(),
+            warnings: tables.errors().cloned().collect(),
+        };
+
+        // elements
+        let element_data = element::get_elements(tables, &mut substance_data.warnings);
+        let mut element_btree = BTreeMap::new();
+        for element in element_data {
+            element_btree.insert(element.z, element);
+        }
+        substance_data.elements = element_btree;
+
+        let mut radiators = Vec::new();
+        let mut absorbers = Vec::new();
+        for element in substance_data.elements.values() {
+            for (n, isotope) in &element.isotopes {
+                if isotope.is_usable {
+                    radiators.push(Substance::Element(element.clone(), *n));
+                }
+            }
+            if element.is_absorber {
+                let most_abundant = element
+                    .isotopes
+                    .values()
+                    .max_by_key(|isotope| isotope.abundance);
+                match most_abundant {
+                    Some(_) if !element.natural_isotopes().is_empty() => {
+                        absorbers.push(Substance::NaturalElement(element.clone()));
+                    }
+                    // synthetic elements are represented by a single isotope
+                    Some(isotope) => {
+                        absorbers.push(Substance::Element(element.clone(), isotope.n));
+                    }
+                    None => substance_data.warnings.push(DataError::file(
+                        "isotope_data.csv",
+                        format!(
+                            "{} has no isotopes and can't be an absorber",
+                            element.symbol
+                        ),
+                    )),
+                }
             }
-            Substance::Compound(compound) => {
-                log::info!("{} Absorber", compound.name);
+        }
+        substance_data.radiators = radiators;
+        substance_data.absorbers = absorbers;
+
+        // compounds
+        let compound_data = compound::get_compounds(tables, &substance_data.elements);
+        let mut compound_btree = BTreeMap::new();
+        for compound in compound_data {
+            compound_btree.insert(compound.name.to_owned(), compound);
+        }
+        substance_data.compounds = compound_btree;
+
+        let mut absorbers = Vec::new();
+        for compound in substance_data.compounds.values() {
+            if compound.is_absorber {
+                absorbers.push(Substance::Compound(compound.clone()));
             }
         }
+        substance_data.absorbers.extend(absorbers);
+
+        // nice logs
+        for e in &substance_data.radiators {
+            match &e {
+                Substance::Element(element, n) => {
+                    let isotope = &element.isotopes[n];
+                    log::info!(
+                        "{} {:?}: {:?} eV, {} photon lines, {:?} Bq/kg",
+                        element.symbol,
+                        element.z + n,
+                        isotope.decays[0].decay_energy,
+                        isotope.photon_lines.len(),
+                        isotope.activity
+                    );
+                }
+                Substance::NaturalElement(element) => {
+                    log::info!("{} nat.", element.symbol);
+                }
+                Substance::Compound(compound) => {
+                    log::info!("{}", &compound.name);
+                }
+            }
+        }
+
+        for e in &substance_data.absorbers {
+            match &e {
+                Substance::Element(element, _) | Substance::NaturalElement(element) => {
+                    log::info!("{} Absorber", element.symbol);
+                }
+                Substance::Compound(compound) => {
+                    log::info!("{} Absorber", compound.name);
+                }
+            }
+        }
+
+        substance_data
+    }
+}
+
+pub fn read_data(
+    mut substance_data: ResMut<SubstanceData>,
+    tables: Res<TableSet>,
+    mode: Res<DataLoadingMode>,
+) {
+    match SubstanceData::load(&tables, *mode) {
+        Ok(data) => *substance_data = data,
+        Err(errors) => {
+            // the other startup systems need the data, stop before they run
+            log::error!("{}", errors);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Sent after the substance data was rebuilt from changed data files,
+/// materials still reference the old substances until they are refreshed
+pub struct SubstanceDataReloaded;
+
+pub fn rebuild_substance_data(
+    tables: Res<TableSet>,
+    mode: Res<DataLoadingMode>,
+    mut substance_data: ResMut<SubstanceData>,
+    mut reloaded: EventWriter<SubstanceDataReloaded>,
+) {
+    if !tables.is_changed() || tables.is_added() {
+        return;
+    }
+
+    match substance_data.rebuild(&tables, *mode) {
+        Ok(()) => {
+            log::info!("Reloaded substance data");
+            reloaded.send(SubstanceDataReloaded);
+        }
+        // the app is already running, keep the old data instead of failing
+        Err(errors) => log::error!("Not reloading substance data: {}", errors),
     }
 }
@@ -0,0 +1,27 @@
+//! Fast neutron removal cross sections, there are no neutron tables.
+//! A removed neutron is slowed down and absorbed close to where it interacted,
+//! so it deposits all its energy there.
+//! All values are in the units of the data files: energies in MeV, cross sections in cm2/g.
+
+/// The cross sections are for fission neutrons, the table has a single entry at this energy
+/// in MeV and is used for all energies
+const FISSION_NEUTRON_ENERGY: f32 = 2.0;
+/// Hydrogen doesn't follow the fit
+const HYDROGEN_REMOVAL_CROSS_SECTION: f64 = 0.598;
+
+/// Mass removal cross section in cm2/g, empirical fits of Zoller (1964)
+/// to the measured values of the elements
+pub fn removal_cross_section(z: f64) -> f64 {
+    if z <= 1.0 {
+        HYDROGEN_REMOVAL_CROSS_SECTION
+    } else if z <= 8.0 {
+        0.19 * z.powf(-0.743)
+    } else {
+        0.125 * z.powf(-0.565)
+    }
+}
+
+/// Table with the removal cross section of an element
+pub fn removal_cross_sections(z: f64) -> Vec<(f32, f32)> {
+    vec![(FISSION_NEUTRON_ENERGY, removal_cross_section(z) as f32)]
+}
@@ -0,0 +1,134 @@
+//! Parametrised photon mass attenuation coefficients, used for elements without tabulated data.
+//! Photoelectric absorption, Compton scattering (Klein–Nishina) and pair production are summed,
+//! the photoelectric part is fitted to the NIST tables and has K and L absorption edges.
+//! All values are in the units of the data files: energies in MeV, coefficients in cm2/g.
+
+use crate::{AVOGADRO_CONSTANT, ELECTRON_MASS};
+
+use super::bethe::{energy_grid, radiation_length, rest_energy};
+
+/// classical electron radius squared in cm2
+const ELECTRON_RADIUS_SQ: f64 = 7.940_787_7e-26;
+/// 1 barn in cm2
+const BARN: f64 = 1e-24;
+
+/// K absorption edge in keV, fitted to Al, Fe, Cu, Pb and U
+pub fn k_edge(z: f64) -> f64 {
+    88.0 * (z / 82.0).powf(2.19)
+}
+
+/// L3 absorption edge in keV, fitted to Fe, W, Pb and U
+pub fn l_edge(z: f64) -> f64 {
+    13.04 * (z / 82.0).powf(2.54)
+}
+
+/// Photoelectric cross section per atom in cm2, `energy` in MeV
+pub fn photoelectric_cross_section(z: f64, energy: f64) -> f64 {
+    let electron_rest_energy = rest_energy(*ELECTRON_MASS);
+    let energy_kev = energy * 1000.0;
+
+    // 40 barn keV^3 * Z^4 / E^3 above the K edge, flattening towards E^-1 at high energies
+    let mut cross_section = 40.0 * z.powi(4) / energy_kev.powi(3)
+        * (1.0 + 1.2 * (energy / electron_rest_energy).powf(1.2));
+
+    let k_edge = k_edge(z);
+    if energy_kev < k_edge {
+        let k_jump = 125.0 / z + 3.5;
+        cross_section *= (energy_kev / k_edge).sqrt() / k_jump;
+    }
+    let l_edge = l_edge(z);
+    if energy_kev < l_edge {
+        let l_jump = 2.4;
+        cross_section *= (energy_kev / l_edge).sqrt() / l_jump;
+    }
+    cross_section * BARN
+}
+
+/// Klein–Nishina cross section per electron in cm2, `energy` in MeV
+pub fn compton_cross_section(energy: f64) -> f64 {
+    let k = energy / rest_energy(*ELECTRON_MASS);
+    let log = (1.0 + 2.0 * k).ln();
+    2.0 * std::f64::consts::PI
+        * ELECTRON_RADIUS_SQ
+        * ((1.0 + k) / (k * k) * (2.0 * (1.0 + k) / (1.0 + 2.0 * k) - log / k) + log / (2.0 * k)
+            - (1.0 + 3.0 * k) / (1.0 + 2.0 * k).powi(2))
+}
+
+/// Pair production cross section per atom in cm2, `energy` in MeV.
+/// The high energy limit 7/9 A/(N_A X0) is reached slowly above the threshold.
+pub fn pair_cross_section(z: f64, nucleon_ratio: f64, energy: f64) -> f64 {
+    let electron_rest_energy = rest_energy(*ELECTRON_MASS);
+    if energy <= 2.0 * electron_rest_energy {
+        return 0.0;
+    }
+    let atomic_mass = z / nucleon_ratio;
+    let high_energy_limit =
+        7.0 / 9.0 * atomic_mass / (*AVOGADRO_CONSTANT * radiation_length(z, nucleon_ratio));
+    let threshold = (1.0 - 2.0 * electron_rest_energy / energy).powi(3);
+    high_energy_limit * 0.13 * threshold * (energy / electron_rest_energy).ln()
+}
+
+/// Share of the photoelectric effect in the interactions of a photon with `energy` in MeV,
+/// pair production is neglected as it only matters far above the absorption edges
+pub fn photoelectric_fraction(z: f64, energy: f64) -> f64 {
+    let photoelectric = photoelectric_cross_section(z, energy);
+    photoelectric / (photoelectric + z * compton_cross_section(energy))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhotonInteraction {
+    Photoelectric,
+    Compton,
+    Pair,
+}
+
+/// Random interaction of a photon with `energy` in MeV with an atom, by the share of each
+/// cross section. The atomic mass for pair production is estimated from `z`.
+pub fn pick_interaction(z: f64, energy: f64) -> PhotonInteraction {
+    let atomic_mass = 2.0 * z + 0.0073 * z * z;
+    let pair = pair_cross_section(z, z / atomic_mass, energy);
+    let other = photoelectric_cross_section(z, energy) + z * compton_cross_section(energy);
+
+    // the rest is split like below the pair production threshold
+    if fastrand::f64() * (pair + other) < pair {
+        PhotonInteraction::Pair
+    } else if fastrand::f64() < photoelectric_fraction(z, energy) {
+        PhotonInteraction::Photoelectric
+    } else {
+        PhotonInteraction::Compton
+    }
+}
+
+/// Mass attenuation coefficients from 1 keV to 20 MeV like the NIST tables,
+/// the absorption edges are listed twice with the values below and above them
+pub fn mass_attenuation_coefficients(z: f64, nucleon_ratio: f64) -> Vec<(f32, f32)> {
+    let atomic_mass = z / nucleon_ratio;
+    let attenuation = |energy: f64| {
+        let cross_section = photoelectric_cross_section(z, energy)
+            + z * compton_cross_section(energy)
+            + pair_cross_section(z, nucleon_ratio, energy);
+        (
+            energy as f32,
+            (cross_section * *AVOGADRO_CONSTANT / atomic_mass) as f32,
+        )
+    };
+
+    let (min, max) = (0.001, 20.0);
+    let mut coefficients = energy_grid(min, max, 20)
+        .into_iter()
+        .map(attenuation)
+        .collect::<Vec<_>>();
+
+    for edge in [k_edge(z), l_edge(z)] {
+        let edge = edge / 1000.0;
+        if edge > min && edge < max {
+            let (_, below) = attenuation(edge * (1.0 - 1e-6));
+            let (_, above) = attenuation(edge * (1.0 + 1e-6));
+            coefficients.push((edge as f32, below));
+            coefficients.push((edge as f32, above));
+        }
+    }
+    // stable, so the value below an edge stays in front
+    coefficients.sort_by(|a, b| a.0.total_cmp(&b.0));
+    coefficients
+}
@@ -0,0 +1,127 @@
+//! Atomic relaxation of a vacancy in the K or L shell, as left by electron capture
+//! or photoelectric absorption. The vacancy is filled by an outer electron and the energy
+//! is carried away by an X-ray or an Auger electron, with the fluorescence yield as the chance
+//! of an X-ray. Binding energies and yields are parametrised by Z, energies are in eV.
+
+use crate::ParticleType;
+
+use super::photon::{k_edge, l_edge};
+
+/// share of electron captures taking a K electron if the decay energy allows it
+const K_CAPTURE_FRACTION: f32 = 0.88;
+/// share of conversion electrons from the K shell if the transition energy allows it
+const K_CONVERSION_FRACTION: f32 = 0.82;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    K,
+    L,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AtomicShells {
+    pub z: usize,
+    /// in eV
+    pub k_binding: f32,
+    /// L3 shell in eV
+    pub l_binding: f32,
+    /// M5 shell in eV
+    pub m_binding: f32,
+    pub k_fluorescence_yield: f32,
+    pub l_fluorescence_yield: f32,
+}
+
+impl AtomicShells {
+    pub fn new(z: usize) -> Self {
+        let z_f64 = z as f64;
+        // Burhop's Z^4 / (a + Z^4) with a fitted to the K and L3 yields of Fe, I and Pb
+        let fluorescence_yield = |a: f64| (z_f64.powi(4) / (a + z_f64.powi(4))) as f32;
+        Self {
+            z,
+            k_binding: (k_edge(z_f64) * 1_000.0) as f32,
+            l_binding: (l_edge(z_f64) * 1_000.0) as f32,
+            // fitted to Pb and U
+            m_binding: (2_480.0 * (z_f64 / 82.0).powf(2.9)) as f32,
+            k_fluorescence_yield: fluorescence_yield(1.12e6),
+            l_fluorescence_yield: fluorescence_yield(8.9e7),
+        }
+    }
+
+    /// The shell an electron is captured from, L if the decay energy in eV is too low for K.
+    /// An unknown decay energy of 0 allows K capture.
+    pub fn capture_shell(&self, decay_energy: f32) -> Shell {
+        if (decay_energy > 0.0 && decay_energy < self.k_binding)
+            || fastrand::f32() > K_CAPTURE_FRACTION
+        {
+            Shell::L
+        } else {
+            Shell::K
+        }
+    }
+
+    /// The shell of a conversion electron for a transition with `energy` in eV
+    pub fn conversion_shell(&self, energy: f32) -> Shell {
+        if energy < self.k_binding || fastrand::f32() > K_CONVERSION_FRACTION {
+            Shell::L
+        } else {
+            Shell::K
+        }
+    }
+
+    /// in eV
+    pub fn binding_energy(&self, shell: Shell) -> f32 {
+        match shell {
+            Shell::K => self.k_binding,
+            Shell::L => self.l_binding,
+        }
+    }
+
+    /// The vacancy left by a photon with `energy` in eV absorbed by the photoelectric effect,
+    /// `None` below the L shell
+    pub fn photoelectric_shell(&self, energy: f32) -> Option<Shell> {
+        if energy < self.l_binding {
+            None
+        } else if energy >= self.k_binding {
+            Shell::K.into()
+        } else {
+            Shell::L.into()
+        }
+    }
+
+    /// X-rays (as gammas) and Auger electrons emitted while a vacancy in `shell` is filled,
+    /// the energy of vacancies in the M shell is neglected
+    pub fn relax(&self, shell: Shell) -> Vec<(ParticleType, f32)> {
+        let mut emitted = Vec::new();
+        let mut l_vacancies = 0;
+        match shell {
+            Shell::K if fastrand::f32() < self.k_fluorescence_yield => {
+                // K alpha
+                emitted.push((ParticleType::Gamma, self.k_binding - self.l_binding));
+                l_vacancies += 1;
+            }
+            // KLL Auger electron
+            Shell::K => {
+                emitted.push((
+                    ParticleType::Electron,
+                    self.k_binding - 2.0 * self.l_binding,
+                ));
+                l_vacancies += 2;
+            }
+            Shell::L => l_vacancies += 1,
+        }
+        for _ in 0..l_vacancies {
+            if fastrand::f32() < self.l_fluorescence_yield {
+                // L alpha
+                emitted.push((ParticleType::Gamma, self.l_binding - self.m_binding));
+            } else {
+                // LMM Auger electron
+                emitted.push((
+                    ParticleType::Electron,
+                    self.l_binding - 2.0 * self.m_binding,
+                ));
+            }
+        }
+        emitted.retain(|(_, energy)| *energy > 0.0);
+        emitted
+    }
+}
@@ -0,0 +1,676 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::Cursor;
+use std::path::Path;
+
+use bevy::{
+    asset::{AssetLoader, HandleId, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::de::DeserializeOwned;
+
+use crate::ParticleType;
+
+use super::{
+    compound::CompoundDataRow,
+    element::{DecayRadiationRow, ElementDataRow, IsotopeDataRow, PhotonLineRow, RADIATION_TYPES},
+    parse_num, DataError, MassAttenuationCoefficientRow, StoppingPowerRow,
+};
+
+/// Path of the data directory inside the assets folder
+pub const DATA_DIRECTORY: &str = "simulation_data";
+/// Directory of the decay radiation exports
+pub const DECAY_RADIATION_DIRECTORY: &str = "decay_radiations";
+
+/// Rows of all data files, the substance data is built from these
+#[derive(Debug, Clone, Default, Resource)]
+pub struct TableSet {
+    pub elements: Vec<ElementDataRow>,
+    pub isotopes: Vec<IsotopeDataRow>,
+    pub compounds: Vec<CompoundDataRow>,
+    pub photon_lines: Vec<PhotonLineRow>,
+    /// rows per file
+    pub decay_radiations: BTreeMap<String, Vec<DecayRadiationRow>>,
+    /// (energy in MeV, MeV*cm2/g | cm2/g) as in the files
+    pub stopping_powers: HashMap<ParticleType, HashMap<TableKey, Vec<(f32, f32)>>>,
+    /// skipped rows per file
+    pub errors: BTreeMap<String, Vec<DataError>>,
+}
+
+/// What a stopping power or attenuation table belongs to, taken from its file name.
+/// `26.csv` is iron, `Concrete.csv` the compound named "Concrete".
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum TableKey {
+    Element(usize),
+    Compound(String),
+}
+
+impl TableKey {
+    pub fn from_stem(stem: &str) -> Self {
+        match stem.parse() {
+            Ok(z) => Self::Element(z),
+            Err(_) => Self::Compound(stem.to_owned()),
+        }
+    }
+}
+
+/// Directory holding the tables for a particle type
+pub fn table_directory(particle_type: ParticleType) -> Option<&'static str> {
+    match particle_type {
+        ParticleType::Alpha => Some("stopping_power_alpha"),
+        ParticleType::Electron => Some("stopping_power_electrons"),
+        ParticleType::Gamma => Some("mass_attenuation_coefficients"),
+        _ => None,
+    }
+}
+
+macro_rules! embed {
+    ($($path:literal),* $(,)?) => {
+        vec![$(($path, &include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/simulation_data/", $path))[..]),)*]
+    };
+}
+
+/// Files compiled into the binary, paths relative to the data directory.
+/// `build.rs` lists the files of the data directory.
+fn embedded_files() -> Vec<(&'static str, &'static [u8])> {
+    include!(concat!(env!("OUT_DIR"), "/embedded_files.rs"))
+}
+
+impl TableSet {
+    /// Tables compiled into the binary
+    pub fn embedded() -> Self {
+        let mut tables = Self::default();
+        for (path, data) in embedded_files() {
+            tables.insert_file(Path::new(path), data);
+        }
+        tables
+    }
+
+    /// Read all csv files in the directory and its sub directories,
+    /// returns the number of files read
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read_dir(&mut self, dir: &Path) -> std::io::Result<usize> {
+        fn visit(tables: &mut TableSet, root: &Path, dir: &Path) -> std::io::Result<usize> {
+            let mut count = 0;
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    count += visit(tables, root, &path)?;
+                } else if path.extension().is_some_and(|extension| extension == "csv") {
+                    let data = std::fs::read(&path)?;
+                    tables.insert_file(path.strip_prefix(root).unwrap(), &data);
+                    count += 1;
+                }
+            }
+            Ok(count)
+        }
+
+        visit(self, dir, dir)
+    }
+
+    /// All errors of all files
+    pub fn errors(&self) -> impl Iterator<Item = &DataError> {
+        self.errors.values().flatten()
+    }
+
+    /// The embedded tables, replaced or extended by the files in the assets folder
+    pub fn load() -> Self {
+        #[allow(unused_mut)]
+        let mut tables = Self::embedded();
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let dir = bevy::asset::FileAssetIo::get_base_path()
+                .join("assets")
+                .join(DATA_DIRECTORY);
+            match tables.read_dir(&dir) {
+                Ok(count) => log::info!("{} data files in {}", count, dir.display()),
+                Err(e) => log::warn!("Using embedded data, can't read {}: {}", dir.display(), e),
+            }
+        }
+
+        tables
+    }
+
+    /// Parse a file, the path is relative to the data directory
+    pub fn insert_file(&mut self, path: &Path, data: &[u8]) {
+        match DataFile::parse(path, data) {
+            Some(DataFile::Elements(table)) => table.apply(self),
+            Some(DataFile::Isotopes(table)) => table.apply(self),
+            Some(DataFile::Compounds(table)) => table.apply(self),
+            Some(DataFile::PhotonLines(table)) => table.apply(self),
+            Some(DataFile::DecayRadiations(table)) => table.apply(self),
+            Some(DataFile::StoppingPower(table)) => table.apply(self),
+            None => {}
+        }
+    }
+
+    /// Undo a data file, the embedded file with the same path is used again if there is one
+    pub fn remove_file(&mut self, file: &str) {
+        if let Some((path, data)) = embedded_files().into_iter().find(|(path, _)| *path == file) {
+            self.insert_file(Path::new(path), data);
+            return;
+        }
+
+        // tables without rows remove their entries
+        match DataFile::parse(Path::new(file), &[]) {
+            Some(DataFile::DecayRadiations(table)) => table.apply(self),
+            Some(DataFile::StoppingPower(table)) => table.apply(self),
+            _ => {}
+        }
+        self.errors.remove(file);
+    }
+
+    /// All stopping power tables (mass attenuation coefficients for gammas) of a particle type
+    pub fn stopping_power_tables(
+        &self,
+        particle_type: ParticleType,
+    ) -> HashMap<TableKey, Vec<(f32, f32)>> {
+        self.stopping_powers
+            .get(&particle_type)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// Checks of a row beyond its types, `Err((column, reason))`
+pub trait ValidateRow {
+    fn validate(&self) -> Result<(), (&'static str, String)> {
+        Ok(())
+    }
+}
+
+/// Rows with errors are skipped and reported
+fn parse_rows<T: DeserializeOwned + ValidateRow>(
+    data: &[u8],
+    file: &str,
+) -> (Vec<T>, Vec<DataError>) {
+    let mut rows = Vec::new();
+    let mut errors = Vec::new();
+
+    let mut data_reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(Cursor::new(data));
+    // the line counting of csv is off by one for \r\n line endings
+    let line_of = |position: &csv::Position| {
+        let byte = (position.byte() as usize).min(data.len());
+        data[..byte].iter().filter(|byte| **byte == b'\n').count() as u64 + 1
+    };
+
+    let headers = match data_reader.headers() {
+        Ok(headers) => headers.clone(),
+        Err(e) => return (rows, vec![DataError::file(file, e.to_string())]),
+    };
+
+    for record in data_reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                errors.push(DataError {
+                    file: file.to_owned(),
+                    line: e.position().map(line_of),
+                    column: None,
+                    reason: e.to_string(),
+                });
+                continue;
+            }
+        };
+        let line = record.position().map(line_of);
+
+        match record.deserialize::<T>(Some(&headers)) {
+            Ok(row) => match row.validate() {
+                Ok(()) => rows.push(row),
+                Err((column, reason)) => errors.push(DataError {
+                    file: file.to_owned(),
+                    line,
+                    column: Some(column.to_owned()),
+                    reason,
+                }),
+            },
+            Err(e) => {
+                let (column, reason) = match e.kind() {
+                    csv::ErrorKind::Deserialize { err, .. } => (
+                        err.field()
+                            .and_then(|field| headers.get(field as usize))
+                            .map(|column| column.to_owned()),
+                        err.kind().to_string(),
+                    ),
+                    _ => (None, e.to_string()),
+                };
+                errors.push(DataError {
+                    file: file.to_owned(),
+                    line,
+                    column,
+                    reason,
+                });
+            }
+        }
+    }
+
+    (rows, errors)
+}
+
+/// A number that has to be present
+pub fn require_num(value: &str, column: &'static str) -> Result<f32, (&'static str, String)> {
+    parse_num(value).map_err(|reason| (column, reason))
+}
+
+/// A number that may be empty, empty fields are 0
+pub fn optional_num(value: &str, column: &'static str) -> Result<f32, (&'static str, String)> {
+    if value.trim().is_empty() {
+        return Ok(0.0);
+    }
+    parse_num(value).map_err(|reason| (column, reason))
+}
+
+impl ValidateRow for StoppingPowerRow {
+    fn validate(&self) -> Result<(), (&'static str, String)> {
+        require_num(&self.energy, "energy")?;
+        require_num(&self.stop_power, "stop_power")?;
+        Ok(())
+    }
+}
+
+impl ValidateRow for MassAttenuationCoefficientRow {
+    fn validate(&self) -> Result<(), (&'static str, String)> {
+        require_num(&self.energy, "energy")?;
+        require_num(&self.yp, "yp")?;
+        Ok(())
+    }
+}
+
+/// A data file that changes the table set when it is loaded
+pub trait DataAsset: bevy::asset::Asset {
+    /// path relative to the data directory
+    fn file(&self) -> &str;
+    fn apply(&self, tables: &mut TableSet);
+    /// whether the table set already holds exactly this file
+    fn is_applied(&self, tables: &TableSet) -> bool;
+}
+
+fn errors_applied(tables: &TableSet, file: &str, errors: &[DataError]) -> bool {
+    tables
+        .errors
+        .get(file)
+        .is_some_and(|applied| applied == errors)
+}
+
+/// `element_data.csv`
+#[derive(Debug, Clone, TypeUuid)]
+#[uuid = "8e0b6f3a-4c2d-4f1e-b7a5-93d2c6e1f407"]
+pub struct ElementTable {
+    pub file: String,
+    pub rows: Vec<ElementDataRow>,
+    pub errors: Vec<DataError>,
+}
+
+impl DataAsset for ElementTable {
+    fn file(&self) -> &str {
+        &self.file
+    }
+
+    fn apply(&self, tables: &mut TableSet) {
+        tables.elements = self.rows.clone();
+        tables.errors.insert(self.file.clone(), self.errors.clone());
+    }
+
+    fn is_applied(&self, tables: &TableSet) -> bool {
+        tables.elements == self.rows && errors_applied(tables, &self.file, &self.errors)
+    }
+}
+
+/// `isotope_data.csv`
+#[derive(Debug, Clone, TypeUuid)]
+#[uuid = "1f6c2b84-9d3e-4a57-8c10-5e7a2f9b3d61"]
+pub struct IsotopeTable {
+    pub file: String,
+    pub rows: Vec<IsotopeDataRow>,
+    pub errors: Vec<DataError>,
+}
+
+impl DataAsset for IsotopeTable {
+    fn file(&self) -> &str {
+        &self.file
+    }
+
+    fn apply(&self, tables: &mut TableSet) {
+        tables.isotopes = self.rows.clone();
+        tables.errors.insert(self.file.clone(), self.errors.clone());
+    }
+
+    fn is_applied(&self, tables: &TableSet) -> bool {
+        tables.isotopes == self.rows && errors_applied(tables, &self.file, &self.errors)
+    }
+}
+
+/// `compound_data.csv`
+#[derive(Debug, Clone, TypeUuid)]
+#[uuid = "a4d8e1c7-3b6f-4e92-b05d-7c1f8a2e6b39"]
+pub struct CompoundTable {
+    pub file: String,
+    pub rows: Vec<CompoundDataRow>,
+    pub errors: Vec<DataError>,
+}
+
+impl DataAsset for CompoundTable {
+    fn file(&self) -> &str {
+        &self.file
+    }
+
+    fn apply(&self, tables: &mut TableSet) {
+        tables.compounds = self.rows.clone();
+        tables.errors.insert(self.file.clone(), self.errors.clone());
+    }
+
+    fn is_applied(&self, tables: &TableSet) -> bool {
+        tables.compounds == self.rows && errors_applied(tables, &self.file, &self.errors)
+    }
+}
+
+/// `photon_lines.csv`
+#[derive(Debug, Clone, TypeUuid)]
+#[uuid = "e27a9c41-8f3b-4d16-a5e0-6b1c9d4f7a23"]
+pub struct PhotonLineTable {
+    pub file: String,
+    pub rows: Vec<PhotonLineRow>,
+    pub errors: Vec<DataError>,
+}
+
+impl DataAsset for PhotonLineTable {
+    fn file(&self) -> &str {
+        &self.file
+    }
+
+    fn apply(&self, tables: &mut TableSet) {
+        tables.photon_lines = self.rows.clone();
+        tables.errors.insert(self.file.clone(), self.errors.clone());
+    }
+
+    fn is_applied(&self, tables: &TableSet) -> bool {
+        tables.photon_lines == self.rows && errors_applied(tables, &self.file, &self.errors)
+    }
+}
+
+/// A decay radiation export in `decay_radiations/`
+#[derive(Debug, Clone, TypeUuid)]
+#[uuid = "3d5f8b20-7e1a-4c93-9b6d-f4a2c8e05b17"]
+pub struct DecayRadiationTable {
+    pub file: String,
+    pub rows: Vec<DecayRadiationRow>,
+    pub errors: Vec<DataError>,
+}
+
+impl DataAsset for DecayRadiationTable {
+    fn file(&self) -> &str {
+        &self.file
+    }
+
+    fn apply(&self, tables: &mut TableSet) {
+        if self.rows.is_empty() {
+            tables.decay_radiations.remove(&self.file);
+        } else {
+            tables
+                .decay_radiations
+                .insert(self.file.clone(), self.rows.clone());
+        }
+        tables.errors.insert(self.file.clone(), self.errors.clone());
+    }
+
+    fn is_applied(&self, tables: &TableSet) -> bool {
+        let rows = tables.decay_radiations.get(&self.file);
+        let rows_applied = if self.rows.is_empty() {
+            rows.is_none()
+        } else {
+            rows == Some(&self.rows)
+        };
+        rows_applied && errors_applied(tables, &self.file, &self.errors)
+    }
+}
+
+/// A stopping power table, or mass attenuation coefficients for gammas
+#[derive(Debug, Clone, TypeUuid)]
+#[uuid = "c93b5e27-6a1d-4f08-9e4c-2b8d7f3a1e56"]
+pub struct StoppingPowerTable {
+    pub file: String,
+    pub particle_type: ParticleType,
+    pub key: TableKey,
+    /// (energy in MeV, MeV*cm2/g | cm2/g)
+    pub values: Vec<(f32, f32)>,
+    pub errors: Vec<DataError>,
+}
+
+impl DataAsset for StoppingPowerTable {
+    fn file(&self) -> &str {
+        &self.file
+    }
+
+    fn apply(&self, tables: &mut TableSet) {
+        let stopping_powers = tables
+            .stopping_powers
+            .entry(self.particle_type)
+            .or_default();
+        // templates without rows
+        if self.values.is_empty() {
+            stopping_powers.remove(&self.key);
+        } else {
+            stopping_powers.insert(self.key.clone(), self.values.clone());
+        }
+        tables.errors.insert(self.file.clone(), self.errors.clone());
+    }
+
+    fn is_applied(&self, tables: &TableSet) -> bool {
+        let values = tables
+            .stopping_powers
+            .get(&self.particle_type)
+            .and_then(|stopping_powers| stopping_powers.get(&self.key));
+        let values_applied = if self.values.is_empty() {
+            values.is_none()
+        } else {
+            values == Some(&self.values)
+        };
+        values_applied && errors_applied(tables, &self.file, &self.errors)
+    }
+}
+
+pub enum DataFile {
+    Elements(ElementTable),
+    Isotopes(IsotopeTable),
+    Compounds(CompoundTable),
+    PhotonLines(PhotonLineTable),
+    DecayRadiations(DecayRadiationTable),
+    StoppingPower(StoppingPowerTable),
+}
+
+impl DataFile {
+    /// The format is chosen by the file name and directory, `None` for unknown files.
+    /// The path is relative to the data directory.
+    pub fn parse(path: &Path, data: &[u8]) -> Option<Self> {
+        let file = path.to_str()?.replace('\\', "/");
+        let name = path.file_name()?.to_str()?;
+        let stem = path.file_stem()?.to_str()?;
+        let dir = path
+            .parent()
+            .and_then(|dir| dir.file_name())
+            .and_then(|dir| dir.to_str())
+            .unwrap_or_default();
+
+        let data_file = match name {
+            "element_data.csv" => {
+                let (rows, errors) = parse_rows(data, &file);
+                Self::Elements(ElementTable { file, rows, errors })
+            }
+            "isotope_data.csv" => {
+                let (rows, errors) = parse_rows(data, &file);
+                Self::Isotopes(IsotopeTable { file, rows, errors })
+            }
+            "compound_data.csv" => {
+                let (rows, errors) = parse_rows(data, &file);
+                Self::Compounds(CompoundTable { file, rows, errors })
+            }
+            "photon_lines.csv" => {
+                let (rows, errors) = parse_rows(data, &file);
+                Self::PhotonLines(PhotonLineTable { file, rows, errors })
+            }
+            _ if dir == DECAY_RADIATION_DIRECTORY => {
+                let (mut rows, mut errors): (Vec<DecayRadiationRow>, _) = parse_rows(data, &file);
+                // the exports of a single radiation type have no type column
+                let file_type = stem.split('_').next().unwrap_or_default();
+                if rows.iter().any(|row| row.rad_type.is_empty()) {
+                    if RADIATION_TYPES.contains(&file_type) {
+                        for row in rows.iter_mut().filter(|row| row.rad_type.is_empty()) {
+                            row.rad_type = file_type.to_owned();
+                        }
+                    } else {
+                        errors.push(DataError::file(
+                            &file,
+                            format!(
+                                "no rad_type column and the file name doesn't start with one of {:?}",
+                                RADIATION_TYPES
+                            ),
+                        ));
+                        rows.retain(|row| !row.rad_type.is_empty());
+                    }
+                }
+                Self::DecayRadiations(DecayRadiationTable { file, rows, errors })
+            }
+            _ => {
+                let particle_type = [
+                    ParticleType::Alpha,
+                    ParticleType::Electron,
+                    ParticleType::Gamma,
+                ]
+                .into_iter()
+                .find(|particle_type| table_directory(*particle_type) == Some(dir))?;
+
+                // technically this is a mass attenuation coeffients but data reading and storing is similar
+                // the rows are validated, parsing can't fail anymore
+                let (values, errors) = if particle_type == ParticleType::Gamma {
+                    let (rows, errors) = parse_rows(data, &file);
+                    let values = rows
+                        .into_iter()
+                        .map(|row: MassAttenuationCoefficientRow| {
+                            (
+                                parse_num(&row.energy).unwrap_or_default(),
+                                parse_num(&row.yp).unwrap_or_default(),
+                            )
+                        })
+                        .collect();
+                    (values, errors)
+                } else {
+                    let (rows, errors) = parse_rows(data, &file);
+                    let values = rows
+                        .into_iter()
+                        .map(|row: StoppingPowerRow| {
+                            (
+                                parse_num(&row.energy).unwrap_or_default(),
+                                parse_num(&row.stop_power).unwrap_or_default(),
+                            )
+                        })
+                        .collect();
+                    (values, errors)
+                };
+
+                Self::StoppingPower(StoppingPowerTable {
+                    file,
+                    particle_type,
+                    key: TableKey::from_stem(stem),
+                    values,
+                    errors,
+                })
+            }
+        };
+        Some(data_file)
+    }
+}
+
+/// Loads all csv formats of the data directory, bevy allows only one loader per extension
+#[derive(Default)]
+pub struct DataFileLoader;
+
+impl AssetLoader for DataFileLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let full_path = load_context.path().to_owned();
+            let path = full_path.strip_prefix(DATA_DIRECTORY).unwrap_or(&full_path);
+            match DataFile::parse(path, bytes) {
+                Some(DataFile::Elements(table)) => {
+                    load_context.set_default_asset(LoadedAsset::new(table))
+                }
+                Some(DataFile::Isotopes(table)) => {
+                    load_context.set_default_asset(LoadedAsset::new(table))
+                }
+                Some(DataFile::Compounds(table)) => {
+                    load_context.set_default_asset(LoadedAsset::new(table))
+                }
+                Some(DataFile::PhotonLines(table)) => {
+                    load_context.set_default_asset(LoadedAsset::new(table))
+                }
+                Some(DataFile::DecayRadiations(table)) => {
+                    load_context.set_default_asset(LoadedAsset::new(table))
+                }
+                Some(DataFile::StoppingPower(table)) => {
+                    load_context.set_default_asset(LoadedAsset::new(table))
+                }
+                None => log::warn!("Unknown data file {}", path.display()),
+            }
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["csv"]
+    }
+}
+
+/// Keeps the data files loaded so changes are picked up
+#[derive(Debug, Default, Resource)]
+pub struct DataHandles(pub Vec<HandleUntyped>);
+
+pub fn load_data_files(asset_server: Res<AssetServer>, mut handles: ResMut<DataHandles>) {
+    // directories can't be listed on the web, the files known at build time are requested instead
+    if cfg!(target_arch = "wasm32") {
+        for (path, _) in embedded_files() {
+            handles
+                .0
+                .push(asset_server.load_untyped(format!("{}/{}", DATA_DIRECTORY, path)));
+        }
+    } else {
+        match asset_server.load_folder(DATA_DIRECTORY) {
+            Ok(folder) => handles.0.extend(folder),
+            Err(e) => log::warn!("Can't watch {}: {}", DATA_DIRECTORY, e),
+        }
+    }
+}
+
+/// Copies loaded or changed data files into the table set.
+/// The files read at startup are loaded by the asset server again, the table set is only
+/// changed, and the substance data rebuilt, if a file differs from what is already applied.
+pub fn apply_data_files<T: DataAsset>(
+    mut events: EventReader<AssetEvent<T>>,
+    assets: Res<Assets<T>>,
+    mut tables: ResMut<TableSet>,
+    mut files: Local<HashMap<HandleId, String>>,
+) {
+    for event in events.iter() {
+        match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+                if let Some(asset) = assets.get(handle) {
+                    files.insert(handle.id(), asset.file().to_owned());
+                    if !asset.is_applied(&tables) {
+                        asset.apply(&mut tables);
+                    }
+                }
+            }
+            AssetEvent::Removed { handle } => {
+                if let Some(file) = files.remove(&handle.id()) {
+                    tables.remove_file(&file);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,166 @@
+//! Scoring meshes that tally deposited energy per voxel, independent of the objects in the scene.
+
+use std::{fmt::Write, sync::atomic::Ordering};
+
+use atomic_float::AtomicF32;
+use bevy::prelude::*;
+
+use crate::EV_CONVERSION;
+
+/// Voxels spanning the unit cube of the entity transform, so translation places and scale
+/// sizes the mesh. Deposits are added from many threads while particles are processed.
+#[derive(Debug, Component)]
+pub struct DoseMesh {
+    /// voxel counts, x changing fastest
+    dimensions: UVec3,
+    /// in eV
+    energy: Vec<AtomicF32>,
+    /// deposited energy divided by the density where it was deposited, in eV m3/kg,
+    /// divided by the voxel volume this is the dose averaged over the voxel
+    energy_per_density: Vec<AtomicF32>,
+}
+
+impl DoseMesh {
+    pub fn new(dimensions: UVec3) -> Self {
+        let dimensions = dimensions.max(UVec3::ONE);
+        let count = (dimensions.x * dimensions.y * dimensions.z) as usize;
+        Self {
+            dimensions,
+            energy: (0..count).map(|_| AtomicF32::new(0.0)).collect(),
+            energy_per_density: (0..count).map(|_| AtomicF32::new(0.0)).collect(),
+        }
+    }
+
+    pub fn dimensions(&self) -> UVec3 {
+        self.dimensions
+    }
+
+    fn index(&self, voxel: UVec3) -> usize {
+        (voxel.x + self.dimensions.x * (voxel.y + self.dimensions.y * voxel.z)) as usize
+    }
+
+    /// Voxel at a point in local space
+    pub fn voxel_at(&self, point: Vec3) -> Option<UVec3> {
+        let relative = (point + 0.5) * self.dimensions.as_vec3();
+        if relative.cmplt(Vec3::ZERO).any() {
+            return None;
+        }
+        let voxel = relative.as_uvec3();
+        if voxel.cmpge(self.dimensions).any() {
+            return None;
+        }
+        Some(voxel)
+    }
+
+    /// `energy` in eV deposited at a point in local space in a material with `density` in kg/m3
+    pub fn add(&self, point: Vec3, energy: f32, density: f32) {
+        if density <= 0.0 {
+            return;
+        }
+        if let Some(voxel) = self.voxel_at(point) {
+            let index = self.index(voxel);
+            self.energy[index].fetch_add(energy, Ordering::Relaxed);
+            self.energy_per_density[index].fetch_add(energy / density, Ordering::Relaxed);
+        }
+    }
+
+    pub fn clear(&self) {
+        for value in self.energy.iter().chain(&self.energy_per_density) {
+            value.store(0.0, Ordering::Relaxed);
+        }
+    }
+
+    /// in m3
+    pub fn voxel_volume(&self, global_transform: &GlobalTransform) -> f32 {
+        let voxel_size =
+            global_transform.compute_transform().scale.abs() / self.dimensions.as_vec3();
+        voxel_size.x * voxel_size.y * voxel_size.z
+    }
+
+    /// in eV
+    pub fn energy(&self, voxel: UVec3) -> f32 {
+        self.energy[self.index(voxel)].load(Ordering::Relaxed)
+    }
+
+    /// Absorbed dose in Gy averaged over the voxel
+    pub fn dose(&self, voxel: UVec3, global_transform: &GlobalTransform) -> f32 {
+        self.energy_per_density[self.index(voxel)].load(Ordering::Relaxed) * *EV_CONVERSION as f32
+            / self.voxel_volume(global_transform)
+    }
+
+    /// Doses in Gy of the voxels with `index` along `axis` (0 = x, 1 = y, 2 = z),
+    /// rows along the next axis, columns along the one after it
+    pub fn slice(&self, axis: usize, index: u32, global_transform: &GlobalTransform) -> Slice {
+        let columns_axis = (axis + 1) % 3;
+        let rows_axis = (axis + 2) % 3;
+        let columns = self.dimensions[columns_axis];
+        let rows = self.dimensions[rows_axis];
+        let index = index.min(self.dimensions[axis] - 1);
+
+        let mut doses = Vec::with_capacity((columns * rows) as usize);
+        for row in 0..rows {
+            for column in 0..columns {
+                let mut voxel = UVec3::ZERO;
+                voxel[axis] = index;
+                voxel[columns_axis] = column;
+                voxel[rows_axis] = row;
+                doses.push(self.dose(voxel, global_transform));
+            }
+        }
+
+        Slice {
+            columns,
+            rows,
+            doses,
+        }
+    }
+
+    /// One line per voxel with its index, center in world space in m, energy in eV and dose in Gy
+    pub fn to_csv(&self, global_transform: &GlobalTransform) -> String {
+        let mut csv = "x_index,y_index,z_index,x,y,z,energy,dose\n".to_owned();
+        for z in 0..self.dimensions.z {
+            for y in 0..self.dimensions.y {
+                for x in 0..self.dimensions.x {
+                    let voxel = UVec3::new(x, y, z);
+                    let center = global_transform
+                        .transform_point((voxel.as_vec3() + 0.5) / self.dimensions.as_vec3() - 0.5);
+                    writeln!(
+                        csv,
+                        "{},{},{},{},{},{},{},{}",
+                        x,
+                        y,
+                        z,
+                        center.x,
+                        center.y,
+                        center.z,
+                        self.energy(voxel),
+                        self.dose(voxel, global_transform)
+                    )
+                    .unwrap();
+                }
+            }
+        }
+        csv
+    }
+}
+
+/// 2D cut through a `DoseMesh`, row major
+#[derive(Debug, Clone)]
+pub struct Slice {
+    pub columns: u32,
+    pub rows: u32,
+    /// in Gy
+    pub doses: Vec<f32>,
+}
+
+impl Slice {
+    /// in Gy
+    pub fn max(&self) -> f32 {
+        self.doses.iter().copied().fold(0.0, f32::max)
+    }
+
+    /// in Gy
+    pub fn dose(&self, column: u32, row: u32) -> f32 {
+        self.doses[(row * self.columns + column) as usize]
+    }
+}
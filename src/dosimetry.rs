@@ -0,0 +1,479 @@
+//! Absorbed and equivalent dose. Objects record the raw deposited energy per particle type,
+//! generation and particle energy, the radiation weighting factors are applied when the dose
+//! is read. Effective doses weight the equivalent doses of tissue tagged phantom regions with
+//! the ICRP 103 tissue weighting factors.
+
+use std::sync::atomic::Ordering;
+
+use atomic_float::AtomicF32;
+use bevy::prelude::*;
+
+use crate::{Generation, Object, Particle, ParticleType, EV_CONVERSION};
+
+/// energy bins per decade of the particle energy
+const BINS_PER_DECADE: usize = 4;
+/// from 1 eV to 10 GeV
+const ENERGY_BINS: usize = 10 * BINS_PER_DECADE;
+
+pub const PARTICLE_TYPES: [ParticleType; 5] = [
+    ParticleType::Alpha,
+    ParticleType::Electron,
+    ParticleType::Proton,
+    ParticleType::Neutron,
+    ParticleType::Gamma,
+];
+
+const GENERATIONS: [Generation; 2] = [Generation::Primary, Generation::Secondary];
+
+/// Start of the energy bins of a particle type and generation
+fn bins_start(particle_type: ParticleType, generation: Generation) -> usize {
+    let type_index = PARTICLE_TYPES
+        .iter()
+        .position(|other| *other == particle_type)
+        .unwrap();
+    let generation_index = GENERATIONS
+        .iter()
+        .position(|other| *other == generation)
+        .unwrap();
+    (generation_index * PARTICLE_TYPES.len() + type_index) * ENERGY_BINS
+}
+
+/// Bin of a particle energy in eV, energies outside of the range go to the first or last bin
+fn energy_bin(energy: f32) -> usize {
+    ((energy.max(1.0).log10() * BINS_PER_DECADE as f32) as usize).min(ENERGY_BINS - 1)
+}
+
+/// Particle energy in eV in the middle of a bin
+fn bin_energy(bin: usize) -> f32 {
+    10f32.powf((bin as f32 + 0.5) / BINS_PER_DECADE as f32)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WeightingModel {
+    Icrp60,
+    #[default]
+    Icrp103,
+}
+
+impl WeightingModel {
+    pub const ALL: [WeightingModel; 2] = [WeightingModel::Icrp60, WeightingModel::Icrp103];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            WeightingModel::Icrp60 => "ICRP 60",
+            WeightingModel::Icrp103 => "ICRP 103",
+        }
+    }
+
+    /// Radiation weighting factor of a particle with `energy` in eV
+    pub fn weighting_factor(&self, particle_type: ParticleType, energy: f32) -> f32 {
+        // neutron curves take MeV
+        let energy = energy / 1_000_000.0;
+        let gauss = |x: f32| (-x.ln().powi(2) / 6.0).exp();
+        match (self, particle_type) {
+            (_, ParticleType::Gamma | ParticleType::Electron) => 1.0,
+            // also used for fission fragments and heavy ions
+            (_, ParticleType::Alpha) => 20.0,
+            (WeightingModel::Icrp60, ParticleType::Proton) => 5.0,
+            (WeightingModel::Icrp103, ParticleType::Proton) => 2.0,
+            (WeightingModel::Icrp60, ParticleType::Neutron) => 5.0 + 17.0 * gauss(2.0 * energy),
+            (WeightingModel::Icrp103, ParticleType::Neutron) => {
+                if energy < 1.0 {
+                    2.5 + 18.2 * gauss(energy)
+                } else if energy <= 50.0 {
+                    5.0 + 17.0 * gauss(2.0 * energy)
+                } else {
+                    2.5 + 3.25 * gauss(0.04 * energy)
+                }
+            }
+        }
+    }
+}
+
+/// Tissues with an ICRP 103 tissue weighting factor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Tissue {
+    RedMarrow,
+    Colon,
+    Lung,
+    Stomach,
+    Breast,
+    Gonads,
+    Bladder,
+    Oesophagus,
+    Liver,
+    Thyroid,
+    BoneSurface,
+    Brain,
+    SalivaryGlands,
+    Skin,
+    /// all other tissues, weighted together
+    Remainder,
+}
+
+impl Tissue {
+    pub const ALL: [Tissue; 15] = [
+        Tissue::RedMarrow,
+        Tissue::Colon,
+        Tissue::Lung,
+        Tissue::Stomach,
+        Tissue::Breast,
+        Tissue::Gonads,
+        Tissue::Bladder,
+        Tissue::Oesophagus,
+        Tissue::Liver,
+        Tissue::Thyroid,
+        Tissue::BoneSurface,
+        Tissue::Brain,
+        Tissue::SalivaryGlands,
+        Tissue::Skin,
+        Tissue::Remainder,
+    ];
+
+    /// Name used in phantom files
+    pub fn key(&self) -> &'static str {
+        match self {
+            Tissue::RedMarrow => "red_marrow",
+            Tissue::Colon => "colon",
+            Tissue::Lung => "lung",
+            Tissue::Stomach => "stomach",
+            Tissue::Breast => "breast",
+            Tissue::Gonads => "gonads",
+            Tissue::Bladder => "bladder",
+            Tissue::Oesophagus => "oesophagus",
+            Tissue::Liver => "liver",
+            Tissue::Thyroid => "thyroid",
+            Tissue::BoneSurface => "bone_surface",
+            Tissue::Brain => "brain",
+            Tissue::SalivaryGlands => "salivary_glands",
+            Tissue::Skin => "skin",
+            Tissue::Remainder => "remainder",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Tissue> {
+        Self::ALL.into_iter().find(|tissue| tissue.key() == key)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tissue::RedMarrow => "Rotes Knochenmark",
+            Tissue::Colon => "Dickdarm",
+            Tissue::Lung => "Lunge",
+            Tissue::Stomach => "Magen",
+            Tissue::Breast => "Brust",
+            Tissue::Gonads => "Keimdrüsen",
+            Tissue::Bladder => "Blase",
+            Tissue::Oesophagus => "Speiseröhre",
+            Tissue::Liver => "Leber",
+            Tissue::Thyroid => "Schilddrüse",
+            Tissue::BoneSurface => "Knochenoberfläche",
+            Tissue::Brain => "Gehirn",
+            Tissue::SalivaryGlands => "Speicheldrüsen",
+            Tissue::Skin => "Haut",
+            Tissue::Remainder => "Restliche Gewebe",
+        }
+    }
+
+    /// ICRP 103 tissue weighting factor, the factors of all tissues sum to 1
+    pub fn weighting_factor(&self) -> f32 {
+        match self {
+            Tissue::RedMarrow
+            | Tissue::Colon
+            | Tissue::Lung
+            | Tissue::Stomach
+            | Tissue::Breast
+            | Tissue::Remainder => 0.12,
+            Tissue::Gonads => 0.08,
+            Tissue::Bladder | Tissue::Oesophagus | Tissue::Liver | Tissue::Thyroid => 0.04,
+            Tissue::BoneSurface | Tissue::Brain | Tissue::SalivaryGlands | Tissue::Skin => 0.01,
+        }
+    }
+}
+
+/// Dose of one tissue, averaged over all regions tagged with it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrganDose {
+    pub tissue: Tissue,
+    /// in kg
+    pub mass: f32,
+    /// in Gy
+    pub absorbed_dose: f32,
+    /// in Sv
+    pub equivalent_dose: f32,
+}
+
+/// Doses of the tissues of an object in the order of `Tissue::ALL`,
+/// tissues without a region in the object are left out
+pub fn organ_doses(
+    object: &Object,
+    global_transform: &GlobalTransform,
+    model: WeightingModel,
+) -> Vec<OrganDose> {
+    Tissue::ALL
+        .iter()
+        .filter_map(|tissue| {
+            let mut mass = 0.0;
+            let mut energy = 0.0;
+            let mut weighted_energy = 0.0;
+            for (i, (region, deposits)) in object
+                .geometry
+                .regions()
+                .iter()
+                .zip(&object.region_absorbed_energy)
+                .enumerate()
+            {
+                if region.tissue == Some(*tissue) {
+                    mass += object.region_mass(i, global_transform);
+                    energy += deposits.total();
+                    weighted_energy += deposits.weighted(model);
+                }
+            }
+
+            (mass > 0.0).then(|| OrganDose {
+                tissue: *tissue,
+                mass,
+                absorbed_dose: energy * *EV_CONVERSION as f32 / mass,
+                equivalent_dose: weighted_energy * *EV_CONVERSION as f32 / mass,
+            })
+        })
+        .collect()
+}
+
+/// Effective dose in Sv, tissues missing from `organ_doses` count with no dose
+pub fn effective_dose(organ_doses: &[OrganDose]) -> f32 {
+    organ_doses
+        .iter()
+        .map(|organ| organ.tissue.weighting_factor() * organ.equivalent_dose)
+        .sum()
+}
+
+/// The radiation weighting model used to show equivalent doses
+#[derive(Debug, Default, Resource)]
+pub struct Dosimetry {
+    pub model: WeightingModel,
+}
+
+/// Raw deposited energy in eV, by particle type, generation and the energy of the particle
+#[derive(Debug, Clone, PartialEq, Reflect, FromReflect)]
+pub struct EnergyDeposits {
+    /// `ENERGY_BINS` per particle type and generation, see `bins_start`
+    deposits: Vec<f32>,
+}
+
+impl Default for EnergyDeposits {
+    fn default() -> Self {
+        Self {
+            deposits: vec![0.0; GENERATIONS.len() * PARTICLE_TYPES.len() * ENERGY_BINS],
+        }
+    }
+}
+
+impl EnergyDeposits {
+    /// `deposit` in eV from a particle with `energy` in eV
+    pub fn add(&mut self, particle: &Particle, energy: f32, deposit: f32) {
+        self.deposits
+            [bins_start(particle.particle_type, particle.generation) + energy_bin(energy)] +=
+            deposit;
+    }
+
+    fn bins(&self, particle_type: ParticleType, generation: Generation) -> &[f32] {
+        let start = bins_start(particle_type, generation);
+        &self.deposits[start..start + ENERGY_BINS]
+    }
+
+    pub fn clear(&mut self) {
+        self.deposits.iter_mut().for_each(|deposit| *deposit = 0.0);
+    }
+
+    /// in eV
+    pub fn total(&self) -> f32 {
+        self.deposits.iter().sum()
+    }
+
+    /// in eV
+    pub fn by_type_and_generation(
+        &self,
+        particle_type: ParticleType,
+        generation: Generation,
+    ) -> f32 {
+        self.bins(particle_type, generation).iter().sum()
+    }
+
+    /// in eV
+    pub fn by_type(&self, particle_type: ParticleType) -> f32 {
+        GENERATIONS
+            .iter()
+            .map(|generation| self.by_type_and_generation(particle_type, *generation))
+            .sum()
+    }
+
+    /// in eV
+    pub fn by_generation(&self, generation: Generation) -> f32 {
+        PARTICLE_TYPES
+            .iter()
+            .map(|particle_type| self.by_type_and_generation(*particle_type, generation))
+            .sum()
+    }
+
+    /// Energy deposited by a particle type multiplied with its radiation weighting factors, in eV
+    pub fn weighted_by_type(&self, particle_type: ParticleType, model: WeightingModel) -> f32 {
+        GENERATIONS
+            .iter()
+            .flat_map(|generation| {
+                self.bins(particle_type, *generation)
+                    .iter()
+                    .enumerate()
+                    .map(|(bin, deposit)| {
+                        deposit * model.weighting_factor(particle_type, bin_energy(bin))
+                    })
+            })
+            .sum()
+    }
+
+    /// Deposited energy multiplied with the radiation weighting factors, in eV
+    pub fn weighted(&self, model: WeightingModel) -> f32 {
+        PARTICLE_TYPES
+            .iter()
+            .map(|particle_type| self.weighted_by_type(*particle_type, model))
+            .sum()
+    }
+
+    /// Absorbed dose in Gy of a `mass` in kg
+    pub fn absorbed_dose(&self, mass: f32) -> f32 {
+        self.total() * *EV_CONVERSION as f32 / mass
+    }
+
+    /// Equivalent dose in Sv of a `mass` in kg
+    pub fn equivalent_dose(&self, mass: f32, model: WeightingModel) -> f32 {
+        self.weighted(model) * *EV_CONVERSION as f32 / mass
+    }
+
+    /// Absorbed dose in Gy of a `mass` in kg from one particle type
+    pub fn absorbed_dose_by_type(&self, particle_type: ParticleType, mass: f32) -> f32 {
+        self.by_type(particle_type) * *EV_CONVERSION as f32 / mass
+    }
+
+    /// Equivalent dose in Sv of a `mass` in kg from one particle type
+    pub fn equivalent_dose_by_type(
+        &self,
+        particle_type: ParticleType,
+        mass: f32,
+        model: WeightingModel,
+    ) -> f32 {
+        self.weighted_by_type(particle_type, model) * *EV_CONVERSION as f32 / mass
+    }
+}
+
+impl std::ops::AddAssign<&EnergyDeposits> for EnergyDeposits {
+    fn add_assign(&mut self, other: &EnergyDeposits) {
+        for (deposit, other) in self.deposits.iter_mut().zip(&other.deposits) {
+            *deposit += other;
+        }
+    }
+}
+
+/// Objects that are dosed as one body, e.g. the parts of a human model
+#[derive(Debug, Clone, Default, Component)]
+pub struct ObjectGroup;
+
+/// Object that is part of the `ObjectGroup` on the entity
+#[derive(Debug, Clone, Copy, Component)]
+pub struct GroupMember(pub Entity);
+
+/// Dose of one part of an `ObjectGroup`, zero for a part without mass
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartDose {
+    pub entity: Entity,
+    /// in kg
+    pub mass: f32,
+    /// in Gy
+    pub absorbed_dose: f32,
+    /// in Sv
+    pub equivalent_dose: f32,
+}
+
+/// Dose of an `ObjectGroup`, the energy of all parts divided by their total mass
+#[derive(Debug, Clone, Default)]
+pub struct GroupDose {
+    /// in kg
+    pub mass: f32,
+    pub absorbed_energy: EnergyDeposits,
+    pub parts: Vec<PartDose>,
+}
+
+impl GroupDose {
+    pub fn new<'a>(
+        parts: impl IntoIterator<Item = (Entity, &'a Object, &'a GlobalTransform)>,
+        model: WeightingModel,
+    ) -> Self {
+        let mut group = Self::default();
+        for (entity, object, global_transform) in parts {
+            let mass = object.mass(global_transform);
+            group.mass += mass;
+            group.absorbed_energy += &object.absorbed_energy;
+            let (absorbed_dose, equivalent_dose) = if mass > 0.0 {
+                (
+                    object.absorbed_energy.absorbed_dose(mass),
+                    object.absorbed_energy.equivalent_dose(mass, model),
+                )
+            } else {
+                (0.0, 0.0)
+            };
+            group.parts.push(PartDose {
+                entity,
+                mass,
+                absorbed_dose,
+                equivalent_dose,
+            });
+        }
+        group
+    }
+
+    /// in Gy, zero for a group without mass
+    pub fn absorbed_dose(&self) -> f32 {
+        if self.mass > 0.0 {
+            self.absorbed_energy.absorbed_dose(self.mass)
+        } else {
+            0.0
+        }
+    }
+
+    /// in Sv, zero for a group without mass
+    pub fn equivalent_dose(&self, model: WeightingModel) -> f32 {
+        if self.mass > 0.0 {
+            self.absorbed_energy.equivalent_dose(self.mass, model)
+        } else {
+            0.0
+        }
+    }
+}
+
+/// `EnergyDeposits` that can be added to from many threads
+pub struct AtomicEnergyDeposits {
+    deposits: Vec<AtomicF32>,
+}
+
+impl Default for AtomicEnergyDeposits {
+    fn default() -> Self {
+        Self {
+            deposits: (0..GENERATIONS.len() * PARTICLE_TYPES.len() * ENERGY_BINS)
+                .map(|_| AtomicF32::new(0.0))
+                .collect(),
+        }
+    }
+}
+
+impl AtomicEnergyDeposits {
+    /// `deposit` in eV from a particle with `energy` in eV
+    pub fn add(&self, particle: &Particle, energy: f32, deposit: f32) {
+        self.deposits[bins_start(particle.particle_type, particle.generation) + energy_bin(energy)]
+            .fetch_add(deposit, Ordering::Relaxed);
+    }
+
+    pub fn add_to(&self, deposits: &mut EnergyDeposits) {
+        for (deposit, atomic) in deposits.deposits.iter_mut().zip(&self.deposits) {
+            *deposit += atomic.load(Ordering::Relaxed);
+        }
+    }
+}
@@ -1,7 +1,9 @@
 use bevy::prelude::*;
 
 use crate::{
-    presets, AmbientMaterial, AssetHandles, LinearSpawner, Object, ResetParticles, SubstanceData,
+    presets, AmbientMaterial, AssetHandles, DetectorSurface, DoseMesh, Geometry, GroupMember,
+    LinearSpawner, Object, ObjectGroup, ResetParticles, SceneGeometry, SceneGeometryPart,
+    SubstanceData, SurfaceDetector,
 };
 
 pub struct RadiationSimEnv;
@@ -11,6 +13,7 @@ impl Plugin for RadiationSimEnv {
         app.add_state::<CurrentEnv>()
             .add_system(spawn_sandbox.in_schedule(OnEnter(CurrentEnv::Sandbox)))
             .add_system(despawn_sandbox.in_schedule(OnExit(CurrentEnv::Sandbox)))
+            .add_system(tag_human_parts)
             .add_system(spawn_experiment.in_schedule(OnEnter(CurrentEnv::Experiment)))
             .add_system(despawn_experiment.in_schedule(OnExit(CurrentEnv::Experiment)));
     }
@@ -94,6 +97,16 @@ fn spawn_sandbox(
     ));
 
     spawn_human(&mut commands, &asset_server, &substance_data);
+
+    // dose distribution in the room around the source and behind the wall
+    commands.spawn((
+        Name::new("Dosisverteilung"),
+        SpatialBundle::from_transform(
+            Transform::from_xyz(0.0, 1.0, 0.0).with_scale(Vec3::new(6.0, 2.0, 6.0)),
+        ),
+        DoseMesh::new(UVec3::new(30, 10, 30)),
+        SandboxObject,
+    ));
 }
 
 fn spawn_human(
@@ -101,46 +114,36 @@ fn spawn_human(
     asset_server: &AssetServer,
     substance_data: &SubstanceData,
 ) {
-    commands
-        .spawn((
-            SceneBundle {
-                scene: asset_server.load("human_model/human.glb#Scene0"),
-                transform: Transform::from_xyz(2.0, 0.0, 0.0),
-                ..default()
-            },
-            Human,
-            HumanRoot,
-            SandboxObject,
-        ))
-        .with_children(|parent| {
-            parent.spawn((
-                Name::new("Main Body"),
-                TransformBundle {
-                    local: Transform::from_xyz(0.0, 0.9, 0.0).with_scale(Vec3::new(0.27, 1.8, 0.2)),
-                    ..Default::default()
-                },
-                Object {
-                    material: presets::water(substance_data),
-                    ..Default::default()
-                },
-                Human,
-                SandboxObject,
-            ));
-
-            parent.spawn((
-                Name::new("Arms"),
-                TransformBundle {
-                    local: Transform::from_xyz(0.0, 1.37, 0.0).with_scale(Vec3::new(1.7, 0.1, 0.1)),
-                    ..Default::default()
-                },
-                Object {
-                    material: presets::water(substance_data),
-                    ..Default::default()
-                },
-                Human,
-                SandboxObject,
-            ));
-        });
+    // the meshes of the model become the absorbing objects once the scene is loaded
+    commands.spawn((
+        Name::new("Mensch"),
+        SceneBundle {
+            scene: asset_server.load("human_model/human.glb#Scene0"),
+            transform: Transform::from_xyz(2.0, 0.0, 0.0),
+            ..default()
+        },
+        SceneGeometry {
+            material: presets::water(substance_data),
+        },
+        Human,
+        HumanRoot,
+        ObjectGroup,
+        SandboxObject,
+    ));
+}
+
+fn tag_human_parts(
+    mut commands: Commands,
+    part_query: Query<(Entity, &SceneGeometryPart), Added<SceneGeometryPart>>,
+    root_query: Query<(), With<HumanRoot>>,
+) {
+    for (entity, part) in part_query.iter() {
+        if root_query.contains(part.root) {
+            commands
+                .entity(entity)
+                .insert((Human, GroupMember(part.root)));
+        }
+    }
 }
 
 fn despawn_sandbox(
@@ -198,6 +201,7 @@ fn spawn_experiment(
             material: presets::pb210(&substance_data),
             ..Default::default()
         },
+        SurfaceDetector::new(DetectorSurface::Boundary(Geometry::Cuboid)),
         ExperimentObject,
         ExperimentTarget,
     ));
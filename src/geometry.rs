@@ -0,0 +1,705 @@
+use std::sync::Arc;
+
+use bevy::{
+    math::DVec2,
+    prelude::*,
+    render::mesh::VertexAttributeValues,
+    render::mesh::{skinning::SkinnedMesh, skinning::SkinnedMeshInverseBindposes, Indices},
+    transform::TransformSystem,
+};
+
+use crate::{MaterialData, Object, Tissue};
+
+/// resolution of the grid used to speed up point in mesh tests
+const MESH_GRID_SIZE: usize = 32;
+
+pub struct RadiationSimGeometry;
+
+impl Plugin for RadiationSimGeometry {
+    fn build(&self, app: &mut App) {
+        app.add_system(
+            extract_scene_geometry
+                .in_base_set(CoreSet::PostUpdate)
+                .after(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// Shape of an `Object` in its local space
+#[derive(Debug, Clone, Default, Reflect, FromReflect)]
+pub enum Geometry {
+    /// unit cube centered at the origin, sized by the transform scale
+    #[default]
+    Cuboid,
+    /// closed triangle mesh
+    TriangleMesh(#[reflect(ignore)] Arc<TriangleMesh>),
+    /// grid of voxels, each belonging to a region with its own material
+    Voxels(#[reflect(ignore)] Arc<VoxelGrid>),
+}
+
+impl Geometry {
+    /// point has to be in the local space of the object
+    pub fn contains(&self, point: Vec3) -> bool {
+        match self {
+            Geometry::Cuboid => point.abs().cmplt(Vec3::splat(0.5)).all(),
+            Geometry::TriangleMesh(mesh) => mesh.contains(point),
+            Geometry::Voxels(grid) => grid.contains(point),
+        }
+    }
+
+    /// index of the region the point is in, only voxel geometries have regions
+    pub fn region_at(&self, point: Vec3) -> Option<usize> {
+        match self {
+            Geometry::Voxels(grid) => grid.region_at(point),
+            _ => None,
+        }
+    }
+
+    pub fn regions(&self) -> &[VoxelRegion] {
+        match self {
+            Geometry::Voxels(grid) => grid.regions(),
+            _ => &[],
+        }
+    }
+
+    /// distance along `direction` to the first surface hit in local space,
+    /// 0 if `origin` is inside a cuboid
+    pub fn ray_intersection(&self, origin: Vec3, direction: Vec3) -> Option<f32> {
+        match self {
+            Geometry::Cuboid => {
+                ray_box_intersection(origin, direction, Vec3::splat(-0.5), Vec3::splat(0.5))
+            }
+            Geometry::TriangleMesh(mesh) => mesh.ray_intersection(origin, direction),
+            Geometry::Voxels(grid) => grid.ray_intersection(origin, direction),
+        }
+    }
+
+    /// in m3, takes the scale of the global transform into account
+    pub fn volume(&self, global_transform: &GlobalTransform) -> f32 {
+        let local_volume = match self {
+            Geometry::Cuboid => 1.0,
+            Geometry::TriangleMesh(mesh) => mesh.volume,
+            Geometry::Voxels(grid) => grid.volume(),
+        };
+        local_volume * global_transform.affine().matrix3.determinant().abs()
+    }
+
+    /// uniformly distributed random point inside the geometry in local space
+    pub fn random_point(&self) -> Vec3 {
+        match self {
+            Geometry::Cuboid => random_point_in_box(Vec3::splat(-0.5), Vec3::splat(0.5)),
+            Geometry::TriangleMesh(mesh) => mesh.random_point(),
+            Geometry::Voxels(grid) => grid.random_point(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TriangleMesh {
+    triangles: Vec<[Vec3; 3]>,
+    min: Vec3,
+    max: Vec3,
+    /// in m3
+    volume: f32,
+    /// triangle indices bucketed by their extent on the y/z plane,
+    /// used for ray casts along the x axis
+    grid: Vec<Vec<u32>>,
+}
+
+impl TriangleMesh {
+    pub fn new(triangles: Vec<[Vec3; 3]>) -> Self {
+        let mut min = Vec3::splat(f32::MAX);
+        let mut max = Vec3::splat(f32::MIN);
+        for vertex in triangles.iter().flatten() {
+            min = min.min(*vertex);
+            max = max.max(*vertex);
+        }
+        if triangles.is_empty() {
+            min = Vec3::ZERO;
+            max = Vec3::ZERO;
+        }
+
+        // divergence theorem, works for closed meshes regardless of the position of the origin
+        let volume = triangles
+            .iter()
+            .map(|[a, b, c]| a.dot(b.cross(*c)) / 6.0)
+            .sum::<f32>()
+            .abs();
+
+        let mut mesh = Self {
+            triangles,
+            min,
+            max,
+            volume,
+            grid: vec![Vec::new(); MESH_GRID_SIZE * MESH_GRID_SIZE],
+        };
+
+        for (i, triangle) in mesh.triangles.iter().enumerate() {
+            let tri_min = triangle[0].min(triangle[1]).min(triangle[2]);
+            let tri_max = triangle[0].max(triangle[1]).max(triangle[2]);
+            let (y_start, z_start) = mesh.grid_cell(tri_min);
+            let (y_end, z_end) = mesh.grid_cell(tri_max);
+            for y in y_start..=y_end {
+                for z in z_start..=z_end {
+                    mesh.grid[y * MESH_GRID_SIZE + z].push(i as u32);
+                }
+            }
+        }
+
+        mesh
+    }
+
+    /// Collect all triangles of a mesh, transformed by `transform`.
+    /// Returns `None` if the mesh has no triangle list with positions.
+    pub fn triangles_from_mesh(mesh: &Mesh, transform: Mat4) -> Option<Vec<[Vec3; 3]>> {
+        let positions = match mesh.attribute(Mesh::ATTRIBUTE_POSITION)? {
+            VertexAttributeValues::Float32x3(positions) => positions,
+            _ => return None,
+        };
+        let positions = positions
+            .iter()
+            .map(|p| transform.transform_point3(Vec3::from(*p)))
+            .collect::<Vec<_>>();
+
+        Some(triangles_from_positions(mesh, &positions))
+    }
+
+    /// Collect all triangles of a skinned mesh in its current pose, in world space.
+    pub fn triangles_from_skinned_mesh(
+        mesh: &Mesh,
+        joint_matrices: &[Mat4],
+    ) -> Option<Vec<[Vec3; 3]>> {
+        let (
+            VertexAttributeValues::Float32x3(positions),
+            VertexAttributeValues::Uint16x4(joint_indices),
+            VertexAttributeValues::Float32x4(joint_weights),
+        ) = (
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)?,
+            mesh.attribute(Mesh::ATTRIBUTE_JOINT_INDEX)?,
+            mesh.attribute(Mesh::ATTRIBUTE_JOINT_WEIGHT)?,
+        )
+        else {
+            return None;
+        };
+
+        let positions = positions
+            .iter()
+            .zip(joint_indices)
+            .zip(joint_weights)
+            .map(|((position, indices), weights)| {
+                let mut skin = Mat4::ZERO;
+                for (index, weight) in indices.iter().zip(weights) {
+                    if let Some(joint_matrix) = joint_matrices.get(*index as usize) {
+                        skin += *joint_matrix * *weight;
+                    }
+                }
+                skin.transform_point3(Vec3::from(*position))
+            })
+            .collect::<Vec<_>>();
+
+        Some(triangles_from_positions(mesh, &positions))
+    }
+
+    pub fn triangles(&self) -> &[[Vec3; 3]] {
+        &self.triangles
+    }
+
+    pub fn bounds(&self) -> (Vec3, Vec3) {
+        (self.min, self.max)
+    }
+
+    /// in m3
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    /// counts the surfaces crossed by a ray along the x axis
+    pub fn contains(&self, point: Vec3) -> bool {
+        if point.cmplt(self.min).any() || point.cmpgt(self.max).any() {
+            return false;
+        }
+
+        let (y, z) = self.grid_cell(point);
+        let crossings = self.grid[y * MESH_GRID_SIZE + z]
+            .iter()
+            .filter(|i| x_ray_crosses_triangle(point, &self.triangles[**i as usize]))
+            .count();
+
+        crossings % 2 == 1
+    }
+
+    /// distance along `direction` to the closest triangle
+    pub fn ray_intersection(&self, origin: Vec3, direction: Vec3) -> Option<f32> {
+        ray_box_intersection(origin, direction, self.min, self.max)?;
+
+        self.triangles
+            .iter()
+            .filter_map(|triangle| ray_triangle_intersection(origin, direction, triangle))
+            .min_by(|a, b| a.total_cmp(b))
+    }
+
+    /// rejection sampling inside the bounding box
+    pub fn random_point(&self) -> Vec3 {
+        for _ in 0..1000 {
+            let point = random_point_in_box(self.min, self.max);
+            if self.contains(point) {
+                return point;
+            }
+        }
+        (self.min + self.max) / 2.0
+    }
+
+    fn grid_cell(&self, point: Vec3) -> (usize, usize) {
+        let size = (self.max - self.min).max(Vec3::splat(f32::EPSILON));
+        let relative = (point - self.min) / size * MESH_GRID_SIZE as f32;
+        (
+            (relative.y.max(0.0) as usize).min(MESH_GRID_SIZE - 1),
+            (relative.z.max(0.0) as usize).min(MESH_GRID_SIZE - 1),
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VoxelRegion {
+    pub id: u16,
+    pub name: String,
+    /// `None` for voxels that are not part of the object
+    pub material: Option<MaterialData>,
+    /// organ the region belongs to for the effective dose
+    pub tissue: Option<Tissue>,
+}
+
+/// Voxels centered around the local origin, x changing fastest
+#[derive(Debug, Clone, Default)]
+pub struct VoxelGrid {
+    dimensions: UVec3,
+    /// in m
+    voxel_size: Vec3,
+    /// index into `regions` per voxel
+    voxels: Vec<u16>,
+    regions: Vec<VoxelRegion>,
+    region_voxel_counts: Vec<usize>,
+}
+
+impl VoxelGrid {
+    pub fn new(
+        dimensions: UVec3,
+        voxel_size: Vec3,
+        voxels: Vec<u16>,
+        regions: Vec<VoxelRegion>,
+    ) -> Self {
+        let mut region_voxel_counts = vec![0; regions.len()];
+        for voxel in &voxels {
+            region_voxel_counts[*voxel as usize] += 1;
+        }
+
+        Self {
+            dimensions,
+            voxel_size,
+            voxels,
+            regions,
+            region_voxel_counts,
+        }
+    }
+
+    pub fn dimensions(&self) -> UVec3 {
+        self.dimensions
+    }
+
+    /// in m
+    pub fn voxel_size(&self) -> Vec3 {
+        self.voxel_size
+    }
+
+    /// in m
+    pub fn size(&self) -> Vec3 {
+        self.dimensions.as_vec3() * self.voxel_size
+    }
+
+    pub fn regions(&self) -> &[VoxelRegion] {
+        &self.regions
+    }
+
+    pub fn regions_mut(&mut self) -> &mut [VoxelRegion] {
+        &mut self.regions
+    }
+
+    /// in m3, without taking the scale of the object into account
+    pub fn region_volume(&self, region: usize) -> f32 {
+        self.region_voxel_counts[region] as f32
+            * self.voxel_size.x
+            * self.voxel_size.y
+            * self.voxel_size.z
+    }
+
+    /// in m3
+    pub fn volume(&self) -> f32 {
+        (0..self.regions.len())
+            .filter(|region| self.regions[*region].material.is_some())
+            .map(|region| self.region_volume(region))
+            .sum()
+    }
+
+    pub fn region_at(&self, point: Vec3) -> Option<usize> {
+        let relative = (point + self.size() / 2.0) / self.voxel_size;
+        if relative.cmplt(Vec3::ZERO).any() {
+            return None;
+        }
+        let index = relative.as_uvec3();
+        if index.cmpge(self.dimensions).any() {
+            return None;
+        }
+
+        let i = index.x + self.dimensions.x * (index.y + self.dimensions.y * index.z);
+        Some(self.voxels[i as usize] as usize)
+    }
+
+    pub fn contains(&self, point: Vec3) -> bool {
+        self.region_at(point)
+            .is_some_and(|region| self.regions[region].material.is_some())
+    }
+
+    /// steps through the grid in half voxel steps
+    pub fn ray_intersection(&self, origin: Vec3, direction: Vec3) -> Option<f32> {
+        let half_size = self.size() / 2.0;
+        let start = ray_box_intersection(origin, direction, -half_size, half_size)?;
+        let step = self.voxel_size.min_element() / 2.0;
+        let direction = direction.normalize();
+
+        let mut t = start;
+        while t < start + self.size().length() {
+            if self.contains(origin + direction * t) {
+                return Some(t);
+            }
+            t += step;
+        }
+        None
+    }
+
+    /// rejection sampling inside the grid
+    pub fn random_point(&self) -> Vec3 {
+        let half_size = self.size() / 2.0;
+        for _ in 0..1000 {
+            let point = random_point_in_box(-half_size, half_size);
+            if self.contains(point) {
+                return point;
+            }
+        }
+        Vec3::ZERO
+    }
+}
+
+fn triangles_from_positions(mesh: &Mesh, positions: &[Vec3]) -> Vec<[Vec3; 3]> {
+    let indices: Vec<usize> = match mesh.indices() {
+        Some(Indices::U16(indices)) => indices.iter().map(|i| *i as usize).collect(),
+        Some(Indices::U32(indices)) => indices.iter().map(|i| *i as usize).collect(),
+        None => (0..positions.len()).collect(),
+    };
+
+    indices
+        .chunks_exact(3)
+        .map(|i| [positions[i[0]], positions[i[1]], positions[i[2]]])
+        .collect()
+}
+
+/// Whether a ray from `origin` along the x axis crosses the triangle, watertight so a ray
+/// through an edge or vertex shared by several triangles is counted exactly once.
+///
+/// The test works on the projection to the y/z plane. Each edge function is evaluated with
+/// its end points in a fixed order, so triangles sharing an edge get exactly opposite values.
+/// A ray exactly on an edge is decided as if `origin` was moved by (0, ε, ε²), which every
+/// triangle at that edge agrees on.
+fn x_ray_crosses_triangle(origin: Vec3, triangle: &[Vec3; 3]) -> bool {
+    let point = DVec2::new(origin.y as f64, origin.z as f64);
+    let edge_function = |a: Vec3, b: Vec3| {
+        let (a, b) = (
+            DVec2::new(a.y as f64, a.z as f64),
+            DVec2::new(b.y as f64, b.z as f64),
+        );
+        let (start, end, sign) = if (a.x, a.y) <= (b.x, b.y) {
+            (a, b, 1.0)
+        } else {
+            (b, a, -1.0)
+        };
+        let edge = end - start;
+        let value = edge.perp_dot(point - start);
+        let value = if value != 0.0 {
+            value
+        } else if edge.y != 0.0 {
+            -edge.y
+        } else {
+            edge.x
+        };
+        value * sign
+    };
+
+    let weights = [
+        edge_function(triangle[1], triangle[2]),
+        edge_function(triangle[2], triangle[0]),
+        edge_function(triangle[0], triangle[1]),
+    ];
+    // either winding of the triangle
+    if !(weights.iter().all(|w| *w > 0.0) || weights.iter().all(|w| *w < 0.0)) {
+        return false;
+    }
+
+    let x = weights
+        .iter()
+        .zip(triangle)
+        .map(|(weight, vertex)| weight * vertex.x as f64)
+        .sum::<f64>()
+        / weights.iter().sum::<f64>();
+    x > origin.x as f64
+}
+
+/// Möller–Trumbore, returns the distance along the ray
+fn ray_triangle_intersection(origin: Vec3, direction: Vec3, triangle: &[Vec3; 3]) -> Option<f32> {
+    let edge_1 = triangle[1] - triangle[0];
+    let edge_2 = triangle[2] - triangle[0];
+    let p = direction.cross(edge_2);
+    let det = edge_1.dot(p);
+    if det.abs() < f32::EPSILON {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+
+    let t_vec = origin - triangle[0];
+    let u = t_vec.dot(p) * inv_det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let q = t_vec.cross(edge_1);
+    let v = direction.dot(q) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let t = edge_2.dot(q) * inv_det;
+    (t > 0.0).then_some(t)
+}
+
+/// slab test, returns the distance along the ray to the box or 0 if the origin is inside
+fn ray_box_intersection(origin: Vec3, direction: Vec3, min: Vec3, max: Vec3) -> Option<f32> {
+    let inv_direction = direction.recip();
+    let t_1 = (min - origin) * inv_direction;
+    let t_2 = (max - origin) * inv_direction;
+    let t_near = t_1.min(t_2).max_element();
+    let t_far = t_1.max(t_2).min_element();
+
+    if t_far < 0.0 || t_near > t_far {
+        None
+    } else {
+        Some(t_near.max(0.0))
+    }
+}
+
+fn random_point_in_box(min: Vec3, max: Vec3) -> Vec3 {
+    min + (max - min) * Vec3::new(fastrand::f32(), fastrand::f32(), fastrand::f32())
+}
+
+/// Turns all meshes of a loaded scene into `Object`s with `Geometry::TriangleMesh`
+#[derive(Debug, Clone, Component)]
+pub struct SceneGeometry {
+    pub material: MaterialData,
+}
+
+/// Object created from the meshes of a `SceneGeometry`
+#[derive(Debug, Clone, Component)]
+pub struct SceneGeometryPart {
+    pub root: Entity,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn extract_scene_geometry(
+    root_query: Query<(Entity, &SceneGeometry)>,
+    children_query: Query<&Children>,
+    mesh_query: Query<(&Handle<Mesh>, &GlobalTransform, Option<&SkinnedMesh>)>,
+    joint_query: Query<&GlobalTransform>,
+    meshes: Res<Assets<Mesh>>,
+    inverse_bindposes: Res<Assets<SkinnedMeshInverseBindposes>>,
+    names: Query<&Name>,
+    mut commands: Commands,
+) {
+    'roots: for (root, scene_geometry) in root_query.iter() {
+        let mesh_entities = children_query
+            .iter_descendants(root)
+            .filter(|entity| mesh_query.contains(*entity))
+            .collect::<Vec<_>>();
+
+        // scene not spawned yet
+        if mesh_entities.is_empty() {
+            continue;
+        }
+
+        let mut parts = Vec::new();
+        for entity in mesh_entities {
+            let (mesh_handle, global_transform, skinned_mesh) = mesh_query.get(entity).unwrap();
+            let Some(mesh) = meshes.get(mesh_handle) else {
+                continue 'roots;
+            };
+
+            let world_to_local = global_transform.compute_matrix().inverse();
+            let triangles = match skinned_mesh {
+                Some(skinned_mesh) => {
+                    let Some(bindposes) = inverse_bindposes.get(&skinned_mesh.inverse_bindposes)
+                    else {
+                        continue 'roots;
+                    };
+                    let joint_matrices = skinned_mesh
+                        .joints
+                        .iter()
+                        .zip(bindposes.iter())
+                        .map(|(joint, bindpose)| {
+                            joint_query
+                                .get(*joint)
+                                .map(|joint| world_to_local * joint.compute_matrix() * *bindpose)
+                                .unwrap_or(Mat4::IDENTITY)
+                        })
+                        .collect::<Vec<_>>();
+                    TriangleMesh::triangles_from_skinned_mesh(mesh, &joint_matrices)
+                }
+                None => TriangleMesh::triangles_from_mesh(mesh, Mat4::IDENTITY),
+            };
+
+            if let Some(triangles) = triangles {
+                parts.push((entity, triangles));
+            }
+        }
+
+        for (entity, triangles) in parts {
+            let mut entity_commands = commands.entity(entity);
+            entity_commands.insert((
+                Object {
+                    material: scene_geometry.material.clone(),
+                    geometry: Geometry::TriangleMesh(Arc::new(TriangleMesh::new(triangles))),
+                    ..Default::default()
+                },
+                SceneGeometryPart { root },
+            ));
+            if !names.contains(entity) {
+                entity_commands.insert(Name::new("Mesh"));
+            }
+        }
+
+        commands.entity(root).remove::<SceneGeometry>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// unit cube centered at the origin, each face split along a diagonal
+    fn cube_mesh() -> TriangleMesh {
+        let corner = |i: usize| {
+            Vec3::new(
+                (i & 1) as f32 - 0.5,
+                ((i >> 1) & 1) as f32 - 0.5,
+                ((i >> 2) & 1) as f32 - 0.5,
+            )
+        };
+        let faces = [
+            [0, 2, 6, 4],
+            [1, 5, 7, 3],
+            [0, 4, 5, 1],
+            [2, 3, 7, 6],
+            [0, 1, 3, 2],
+            [4, 6, 7, 5],
+        ];
+        let triangles = faces
+            .iter()
+            .flat_map(|[a, b, c, d]| {
+                [
+                    [corner(*a), corner(*b), corner(*c)],
+                    [corner(*a), corner(*c), corner(*d)],
+                ]
+            })
+            .collect();
+        TriangleMesh::new(triangles)
+    }
+
+    fn voxel_grid() -> VoxelGrid {
+        let region = |id, material: Option<MaterialData>| VoxelRegion {
+            id,
+            name: String::new(),
+            material,
+            tissue: None,
+        };
+        // 2x1x1 voxels of 1 cm, the voxel at negative x is outside the object
+        VoxelGrid::new(
+            UVec3::new(2, 1, 1),
+            Vec3::splat(0.01),
+            vec![0, 1],
+            vec![
+                region(0, None),
+                region(1, Some(MaterialData::new(Vec::new()))),
+            ],
+        )
+    }
+
+    #[test]
+    fn mesh_volume() {
+        assert!((cube_mesh().volume() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn point_in_mesh() {
+        let mesh = cube_mesh();
+        assert!(mesh.contains(Vec3::new(0.1, 0.3, -0.2)));
+        assert!(!mesh.contains(Vec3::new(0.1, 0.7, -0.2)));
+        assert!(!mesh.contains(Vec3::new(-0.6, 0.3, -0.2)));
+    }
+
+    #[test]
+    fn ray_through_diagonal_edge() {
+        let mesh = cube_mesh();
+        // the rays along x go exactly through the diagonals of both x faces
+        assert!(mesh.contains(Vec3::ZERO));
+        assert!(mesh.contains(Vec3::new(0.2, -0.25, -0.25)));
+        assert!(!mesh.contains(Vec3::new(0.6, 0.1, 0.1)));
+        assert!(!mesh.contains(Vec3::new(-0.6, 0.1, 0.1)));
+    }
+
+    #[test]
+    fn mesh_matches_cuboid() {
+        let mesh = cube_mesh();
+        for _ in 0..10_000 {
+            let point = random_point_in_box(Vec3::splat(-0.75), Vec3::splat(0.75));
+            assert_eq!(mesh.contains(point), Geometry::Cuboid.contains(point));
+        }
+    }
+
+    #[test]
+    fn ray_intersections() {
+        let origin = Vec3::new(-2.0, 0.1, 0.2);
+        let cuboid = Geometry::Cuboid.ray_intersection(origin, Vec3::X).unwrap();
+        assert!((cuboid - 1.5).abs() < 1e-6);
+
+        let mesh = Geometry::TriangleMesh(Arc::new(cube_mesh()));
+        let hit = mesh.ray_intersection(origin, Vec3::X).unwrap();
+        assert!((hit - 1.5).abs() < 1e-6);
+        assert!(mesh.ray_intersection(origin, -Vec3::X).is_none());
+
+        // starts outside the grid, the first voxel has no material
+        let grid = voxel_grid();
+        let hit = grid
+            .ray_intersection(Vec3::new(-0.02, 0.0, 0.0), Vec3::X)
+            .unwrap();
+        assert!((0.0195..0.0255).contains(&hit));
+        assert!(grid
+            .ray_intersection(Vec3::new(-0.02, 0.0, 0.0), Vec3::Y)
+            .is_none());
+    }
+
+    #[test]
+    fn voxel_regions() {
+        let grid = voxel_grid();
+        assert_eq!(grid.region_at(Vec3::new(-0.005, 0.0, 0.0)), Some(0));
+        assert_eq!(grid.region_at(Vec3::new(0.005, 0.0, 0.0)), Some(1));
+        assert_eq!(grid.region_at(Vec3::new(0.015, 0.0, 0.0)), None);
+        assert!(!grid.contains(Vec3::new(-0.005, 0.0, 0.0)));
+        assert!(grid.contains(Vec3::new(0.005, 0.004, -0.004)));
+        assert!((grid.volume() - 1e-6).abs() < 1e-12);
+    }
+}
@@ -5,14 +5,22 @@ use bevy::{
 
 pub mod constants;
 pub use constants::*;
-mod data_reading;
+pub mod data_reading;
 use data_reading::*;
+pub mod dose_mesh;
+use dose_mesh::*;
+pub mod dosimetry;
+use dosimetry::*;
 mod env;
 use env::*;
+pub mod geometry;
+use geometry::*;
 pub mod material;
 use material::*;
 mod particle;
 use particle::*;
+pub mod phantom;
+use phantom::*;
 mod render;
 mod ui;
 use ui::*;
@@ -21,6 +29,7 @@ use ui::*;
 pub struct InterfaceState {
     advanced: bool,
     edit_objects: bool,
+    dose_mesh: bool,
 }
 
 #[derive(Debug, Resource, Default)]
@@ -37,12 +46,16 @@ impl Plugin for RadiationSim {
         app.add_plugin(RadiationSimUI)
             .add_plugin(RadiationSimEnv)
             .add_plugin(RadiationSimParticle)
+            .add_plugin(RadiationSimGeometry)
+            .add_plugin(RadiationSimPhantom)
             .insert_resource(InterfaceState {
                 // in debug builds show advanced default
                 advanced: cfg!(debug_assertions),
                 edit_objects: cfg!(debug_assertions),
+                dose_mesh: false,
             })
             .init_resource::<AssetHandles>()
+            .init_resource::<Dosimetry>()
             .insert_resource(AmbientLight {
                 brightness: 0.1,
                 color: Color::rgb(1.0, 1.0, 1.0),
@@ -175,12 +188,18 @@ fn move_camera(
 pub fn run() {
     App::new()
         .insert_resource(ClearColor(Color::rgb(0.9, 0.9, 0.9)))
+        .insert_resource(DataLoadingMode::from_args())
         .add_plugins(
             DefaultPlugins
                 .set(bevy::log::LogPlugin {
                     level: bevy::log::Level::INFO,
                     filter: "spawn=trace,wgpu_core=warn,wgpu_hal=error,bevy_ecs=error".to_string(),
                 })
+                .set(AssetPlugin {
+                    // reload changed data files, the web has no file system to watch
+                    watch_for_changes: cfg!(not(target_arch = "wasm32")),
+                    ..default()
+                })
                 .set(WindowPlugin {
                     primary_window: Some(Window {
                         title: "Radiation Simulation".into(),
@@ -1,35 +1,260 @@
+use std::collections::HashMap;
+
 use bevy::prelude::*;
 
-use crate::data_reading::Substance;
+use crate::{
+    data_reading::{Isotope, Substance, SubstanceData},
+    ParticleType, StoppingPower,
+};
 
 pub mod presets;
 
 #[derive(Debug, Clone, Reflect)]
 pub struct MaterialData {
-    pub parts: Vec<(f32, Substance)>,
+    /// volume fraction and substance, set with `set_parts` to keep the cached values current
+    parts: Vec<(f32, Substance)>,
+    /// in kg/m3, replaces the density of the substances
+    density_override: Option<f32>,
+    /// in kg/m3, cached because it is needed for every step of every particle
+    average_density: f32,
+    /// in kg/m3, the override if set
+    density: f32,
+    /// effective mass stopping powers of the mixture
+    #[reflect(ignore)]
+    stopping_powers: HashMap<ParticleType, StoppingPower>,
+    /// particle types for which only some of the parts have stopping powers
+    #[reflect(ignore)]
+    incomplete_stopping_powers: Vec<ParticleType>,
+    /// atomic number and cumulative mass fraction of every element of the parts,
+    /// the last fraction is 1
+    #[reflect(ignore)]
+    cumulative_element_fractions: Vec<(usize, f32)>,
 }
 
 impl MaterialData {
-    pub fn pick_substance(&self) -> Substance {
-        let num = fastrand::f32();
-        let mut acc = 0.0;
-        let mut i = 0;
-
-        loop {
-            if self.parts[i].0 + acc > num {
-                break self.parts[i].1.clone();
-            }
+    pub fn new(parts: Vec<(f32, Substance)>) -> Self {
+        let mut material = Self {
+            parts,
+            density_override: None,
+            average_density: 0.0,
+            density: 0.0,
+            stopping_powers: HashMap::new(),
+            incomplete_stopping_powers: Vec::new(),
+            cumulative_element_fractions: Vec::new(),
+        };
+        material.update_stopping_powers();
+        material
+    }
 
-            acc += self.parts[i].0;
-            i += 1;
-        }
+    pub fn parts(&self) -> &[(f32, Substance)] {
+        &self.parts
+    }
+
+    /// Replace the parts and update the density, stopping powers and element fractions
+    pub fn set_parts(&mut self, parts: Vec<(f32, Substance)>) {
+        self.parts = parts;
+        self.update_stopping_powers();
+    }
+
+    /// Atomic number of a random element weighted by its mass fraction,
+    /// `None` if the composition isn't known
+    pub fn pick_element(&self) -> Option<usize> {
+        let pick = fastrand::f32();
+        let i = self
+            .cumulative_element_fractions
+            .partition_point(|(_, fraction)| *fraction <= pick);
+        self.cumulative_element_fractions
+            .get(i)
+            .or(self.cumulative_element_fractions.last())
+            .map(|(z, _)| *z)
     }
 
+    /// in kg/m3, volume weighted density of the parts
     pub fn average_density(&self) -> f32 {
-        self.parts
+        self.average_density
+    }
+
+    /// in kg/m3, the override if set
+    pub fn density(&self) -> f32 {
+        self.density
+    }
+
+    /// in kg/m3
+    pub fn density_override(&self) -> Option<f32> {
+        self.density_override
+    }
+
+    pub fn set_density_override(&mut self, density_override: Option<f32>) {
+        self.density_override = density_override;
+        self.update_density();
+    }
+
+    fn update_density(&mut self) {
+        self.average_density = self
+            .parts
             .iter()
             .map(|(amount, substance)| substance.density() * amount)
-            .sum()
+            .sum();
+        self.density = self.density_override.unwrap_or(self.average_density);
+    }
+
+    /// mass fraction of each part, the amounts of the parts are volume fractions
+    pub fn mass_fractions(&self) -> Vec<f32> {
+        let average_density = self.average_density();
+        self.parts
+            .iter()
+            .map(|(amount, substance)| {
+                if average_density > 0.0 {
+                    amount * substance.density() / average_density
+                } else {
+                    *amount
+                }
+            })
+            .collect()
+    }
+
+    /// The isotopes that can decay with their activity in Bq per kg of the material
+    pub fn radioactive_isotopes(&self) -> Vec<(&Isotope, f32)> {
+        self.parts
+            .iter()
+            .zip(self.mass_fractions())
+            .flat_map(|((_, substance), mass_fraction)| {
+                substance
+                    .radioactive_isotopes()
+                    .into_iter()
+                    .map(move |(isotope, activity)| (isotope, activity * mass_fraction))
+            })
+            .collect()
+    }
+
+    /// Combine the mass stopping powers of all parts using Bragg additivity.
+    /// Parts without a table for a particle type are left out of the sum for that type.
+    /// Also updates the cached density and element fractions.
+    fn update_stopping_powers(&mut self) {
+        self.update_density();
+        self.stopping_powers.clear();
+        self.incomplete_stopping_powers.clear();
+        let mass_fractions = self.mass_fractions();
+        self.update_element_fractions(&mass_fractions);
+
+        for particle_type in [
+            ParticleType::Alpha,
+            ParticleType::Electron,
+            ParticleType::Proton,
+            ParticleType::Neutron,
+            ParticleType::Gamma,
+        ] {
+            let tables = self
+                .parts
+                .iter()
+                .zip(&mass_fractions)
+                .filter_map(|((_, substance), mass_fraction)| {
+                    substance
+                        .stopping_powers(particle_type)
+                        .map(|table| (*mass_fraction, table))
+                })
+                .collect::<Vec<_>>();
+
+            if tables.is_empty() {
+                continue;
+            }
+
+            if tables.len() < self.parts.len() {
+                log::warn!(
+                    "Missing {:?} stopping powers for some parts of {}, using the others only",
+                    particle_type,
+                    self.parts
+                        .iter()
+                        .map(|(_, substance)| substance.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+                self.incomplete_stopping_powers.push(particle_type);
+            }
+
+            // single substances keep their table as is
+            if let [(mass_fraction, table)] = tables[..] {
+                if mass_fraction == 1.0 {
+                    self.stopping_powers.insert(particle_type, table.clone());
+                    continue;
+                }
+            }
+
+            let mut energies = tables
+                .iter()
+                .flat_map(|(_, table)| table.iter().map(|(energy, _)| *energy))
+                .collect::<Vec<_>>();
+            energies.sort_by(|a, b| a.total_cmp(b));
+            energies.dedup();
+
+            let combined = energies
+                .into_iter()
+                .map(|energy| {
+                    let stopping_power = tables
+                        .iter()
+                        .map(|(mass_fraction, table)| {
+                            mass_fraction * interpolate_stopping_power(table, energy)
+                        })
+                        .sum();
+                    (energy, stopping_power)
+                })
+                .collect();
+
+            self.stopping_powers.insert(particle_type, combined);
+        }
+    }
+
+    fn update_element_fractions(&mut self, mass_fractions: &[f32]) {
+        let elements = self
+            .parts
+            .iter()
+            .zip(mass_fractions)
+            .flat_map(|((_, substance), mass_fraction)| {
+                substance
+                    .elements()
+                    .into_iter()
+                    .map(move |(z, fraction)| (z, fraction * mass_fraction))
+            })
+            .collect::<Vec<_>>();
+        let total: f32 = elements.iter().map(|(_, fraction)| fraction).sum();
+
+        self.cumulative_element_fractions.clear();
+        if total <= 0.0 {
+            return;
+        }
+        let mut cumulative = 0.0;
+        for (z, fraction) in elements {
+            cumulative += fraction / total;
+            self.cumulative_element_fractions.push((z, cumulative));
+        }
+    }
+
+    /// Point to the substances of reloaded substance data,
+    /// substances that no longer exist are kept as they are
+    pub fn refresh(&mut self, substance_data: &SubstanceData) {
+        for (_, substance) in &mut self.parts {
+            if let Some(new) = substance_data.find_same(substance) {
+                *substance = new;
+            }
+        }
+        self.update_stopping_powers();
+    }
+
+    /// Particle types whose stopping power ignores some of the parts
+    pub fn incomplete_stopping_powers(&self) -> &[ParticleType] {
+        &self.incomplete_stopping_powers
+    }
+
+    /// in eV/m or 1/m for gammas and neutrons,
+    /// `None` if the material does not interact with the particle
+    pub fn stopping_power(&self, particle_type: ParticleType, energy: f32) -> Option<f32> {
+        // 1 eV*m2/kg * 1 kg/m3 = 1 eV/m
+        // 1 m2/kg * 1 kg/m3 = 1/m
+        self.stopping_powers
+            .get(&particle_type)
+            .map(|stopping_powers| {
+                interpolate_stopping_power(stopping_powers, energy) * self.density()
+            })
     }
 }
 
@@ -38,3 +263,20 @@ impl Default for MaterialData {
         presets::vacuum()
     }
 }
+
+/// linear interpolation between the neighbouring entries found by binary search,
+/// clamped to the first and last entry
+pub fn interpolate_stopping_power(stopping_powers: &StoppingPower, energy: f32) -> f32 {
+    let i = stopping_powers.partition_point(|(stop_energy, _)| *stop_energy < energy);
+    if i == 0 {
+        return stopping_powers[0].1;
+    }
+    if i == stopping_powers.len() {
+        return stopping_powers[i - 1].1;
+    }
+
+    let (energy_0, stopping_power_0) = stopping_powers[i - 1];
+    let (energy_1, stopping_power_1) = stopping_powers[i];
+    let t = (energy - energy_0) / (energy_1 - energy_0);
+    stopping_power_0 + (stopping_power_1 - stopping_power_0) * t
+}
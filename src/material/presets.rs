@@ -3,49 +3,54 @@ use crate::{Substance, SubstanceData};
 use super::MaterialData;
 
 pub fn h3(data: &SubstanceData) -> MaterialData {
-    MaterialData {
-        parts: vec![(1.0, Substance::Element(data.elements[&1].clone(), 2))],
-    }
+    MaterialData::new(vec![(
+        1.0,
+        Substance::Element(data.elements[&1].clone(), 2),
+    )])
 }
 
 pub fn pb208(data: &SubstanceData) -> MaterialData {
-    MaterialData {
-        parts: vec![(1.0, Substance::Element(data.elements[&82].clone(), 126))],
-    }
+    MaterialData::new(vec![(
+        1.0,
+        Substance::Element(data.elements[&82].clone(), 126),
+    )])
 }
 
 pub fn pb210(data: &SubstanceData) -> MaterialData {
-    MaterialData {
-        parts: vec![(1.0, Substance::Element(data.elements[&82].clone(), 128))],
-    }
+    MaterialData::new(vec![(
+        1.0,
+        Substance::Element(data.elements[&82].clone(), 128),
+    )])
 }
 
 pub fn pu239(data: &SubstanceData) -> MaterialData {
-    MaterialData {
-        parts: vec![(1.0, Substance::Element(data.elements[&94].clone(), 145))],
-    }
+    MaterialData::new(vec![(
+        1.0,
+        Substance::Element(data.elements[&94].clone(), 145),
+    )])
+}
+
+pub fn germanium(data: &SubstanceData) -> MaterialData {
+    MaterialData::new(vec![(
+        1.0,
+        Substance::NaturalElement(data.elements[&32].clone()),
+    )])
 }
 
 pub fn air(data: &SubstanceData) -> MaterialData {
-    MaterialData {
-        parts: vec![(
-            1.0,
-            Substance::Compound(data.compounds[&"Air".to_owned()].clone()),
-        )],
-    }
+    MaterialData::new(vec![(
+        1.0,
+        Substance::Compound(data.compounds[&"Air".to_owned()].clone()),
+    )])
 }
 
 pub fn water(data: &SubstanceData) -> MaterialData {
-    MaterialData {
-        parts: vec![(
-            1.0,
-            Substance::Compound(data.compounds[&"Water".to_owned()].clone()),
-        )],
-    }
+    MaterialData::new(vec![(
+        1.0,
+        Substance::Compound(data.compounds[&"Water".to_owned()].clone()),
+    )])
 }
 
 pub fn vacuum() -> MaterialData {
-    MaterialData {
-        parts: vec![(1.0, Substance::default())],
-    }
+    MaterialData::new(vec![(1.0, Substance::default())])
 }
@@ -1,11 +1,23 @@
-use std::sync::atomic::Ordering;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
 
 use atomic_float::AtomicF32;
+
 use bevy::{prelude::*, render::view::NoFrustumCulling};
 
 use crate::{
-    element, render, CurrentEnv, MaterialData, RadiationSimData, StoppingPower, Substance,
-    ALPHA_MASS, ELECTRON_MASS, EV_CONVERSION, LIGHT_SPEED, LIGHT_SPEED_SQ,
+    dosimetry::{AtomicEnergyDeposits, EnergyDeposits, PARTICLE_TYPES},
+    element::{DecayType, PhotonType},
+    photon::{pick_interaction, PhotonInteraction},
+    relaxation::AtomicShells,
+    render, CurrentEnv, DoseMesh, Geometry, Isotope, MaterialData, RadiationSimData, SceneGeometry,
+    SubstanceData, SubstanceDataReloaded, ALPHA_MASS, ELECTRON_MASS, EV_CONVERSION, LIGHT_SPEED,
+    LIGHT_SPEED_SQ, NEUTRON_MASS, PROTON_MASS,
 };
 
 #[derive(Debug, Clone, PartialEq, PartialOrd, Default, Component, Reflect)]
@@ -13,6 +25,24 @@ use crate::{
 pub struct Particle {
     pub particle_type: ParticleType,
     pub energy: f32,
+    pub generation: Generation,
+    /// primary history (event) the particle belongs to, secondaries keep the one of their primary
+    pub history: u64,
+}
+
+static NEXT_HISTORY: AtomicU64 = AtomicU64::new(1);
+
+/// Id for the particles of a new decay or source particle
+fn new_history() -> u64 {
+    NEXT_HISTORY.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Whether a particle comes from a decay or was created by another particle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Hash, Reflect)]
+pub enum Generation {
+    #[default]
+    Primary,
+    Secondary,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Hash, Reflect)]
@@ -34,7 +64,235 @@ pub struct AmbientMaterial {
 #[reflect(Component)]
 pub struct Object {
     pub material: MaterialData,
-    pub absorbed_energy: f32,
+    pub geometry: Geometry,
+    pub absorbed_energy: EnergyDeposits,
+    /// per region of the geometry
+    pub region_absorbed_energy: Vec<EnergyDeposits>,
+    /// replaces the activity derived from the material and size
+    pub source_activity: Option<SourceActivity>,
+}
+
+/// Total activity of a source as given on its certificate
+#[derive(Debug, Clone, Copy, PartialEq, Reflect, FromReflect)]
+pub struct SourceActivity {
+    /// in Bq at the reference date
+    pub activity: f32,
+    /// date the activity was measured
+    pub reference_date: Date,
+    /// unit the activity is shown in
+    pub unit: ActivityUnit,
+}
+
+impl SourceActivity {
+    /// Distribute the activity over the isotopes in the ratio of `isotopes`
+    /// and let each decay with its half life from the reference date to `start_date`,
+    /// the date the simulation started, and `time_passed` in s since then
+    pub fn isotope_activities<'a>(
+        &self,
+        isotopes: &[(&'a Isotope, f32)],
+        start_date: Date,
+        time_passed: f32,
+    ) -> Vec<(&'a Isotope, f32)> {
+        let total: f32 = isotopes.iter().map(|(_, activity)| activity).sum();
+        if total <= 0.0 {
+            return Vec::new();
+        }
+        let time = self.reference_date.seconds_until(start_date) + time_passed;
+        isotopes
+            .iter()
+            .map(|(isotope, activity)| {
+                (
+                    *isotope,
+                    self.activity * activity / total * decay_factor(isotope, time),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Share of the activity left after `time` in s
+fn decay_factor(isotope: &Isotope, time: f32) -> f32 {
+    isotope.half_life.map_or(1.0, |half_life| {
+        (-std::f64::consts::LN_2 * time as f64 / half_life.0 as f64).exp() as f32
+    })
+}
+
+/// A calendar date of the gregorian calendar
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Reflect, FromReflect)]
+pub struct Date {
+    pub year: i32,
+    /// 1 to 12
+    pub month: u32,
+    /// 1 to 31
+    pub day: u32,
+}
+
+impl Date {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn today() -> Self {
+        let seconds = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        Self::from_days(seconds as i64 / 86_400)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn today() -> Self {
+        let date = js_sys::Date::new_0();
+        Self {
+            year: date.get_full_year() as i32,
+            month: date.get_month() + 1,
+            day: date.get_date(),
+        }
+    }
+
+    /// Days since 1970-01-01, Howard Hinnant's days_from_civil
+    pub fn days(&self) -> i64 {
+        let year = self.year as i64 - i64::from(self.month <= 2);
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let month = (self.month as i64 + 9) % 12;
+        let day_of_year = (153 * month + 2) / 5 + self.day as i64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146_097 + day_of_era - 719_468
+    }
+
+    /// The date `days` after 1970-01-01, Howard Hinnant's civil_from_days
+    pub fn from_days(days: i64) -> Self {
+        let days = days + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days - era * 146_097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month + 2) / 5 + 1;
+        let month = if month < 10 { month + 3 } else { month - 9 };
+        Self {
+            year: (year_of_era + era * 400 + i64::from(month <= 2)) as i32,
+            month: month as u32,
+            day: day as u32,
+        }
+    }
+
+    pub fn days_in_month(&self) -> u32 {
+        let next_month = if self.month == 12 {
+            Self {
+                year: self.year + 1,
+                month: 1,
+                day: 1,
+            }
+        } else {
+            Self {
+                month: self.month + 1,
+                day: 1,
+                ..*self
+            }
+        };
+        (next_month.days() - Self { day: 1, ..*self }.days()) as u32
+    }
+
+    /// in s, negative if `other` is before this date
+    pub fn seconds_until(&self, other: Date) -> f32 {
+        (other.days() - self.days()) as f32 * 86_400.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Reflect, FromReflect)]
+pub enum ActivityUnit {
+    #[default]
+    Becquerel,
+    Curie,
+}
+
+impl ActivityUnit {
+    /// in Bq
+    pub fn value(&self) -> f32 {
+        match self {
+            ActivityUnit::Becquerel => 1.0,
+            ActivityUnit::Curie => 3.7e10,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            ActivityUnit::Becquerel => "Bq",
+            ActivityUnit::Curie => "Ci",
+        }
+    }
+}
+
+impl Object {
+    /// material at a point in local space, regions override the material of the object
+    pub fn material_at(&self, point: Vec3) -> &MaterialData {
+        self.geometry
+            .region_at(point)
+            .and_then(|region| self.geometry.regions()[region].material.as_ref())
+            .unwrap_or(&self.material)
+    }
+
+    /// in kg
+    pub fn mass(&self, global_transform: &GlobalTransform) -> f32 {
+        match &self.geometry {
+            Geometry::Voxels(grid) => {
+                let scale = global_transform.affine().matrix3.determinant().abs();
+                grid.regions()
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, region)| {
+                        let material = region.material.as_ref()?;
+                        Some(grid.region_volume(i) * scale * material.density())
+                    })
+                    .sum()
+            }
+            _ => self.geometry.volume(global_transform) * self.material.density(),
+        }
+    }
+
+    /// The isotopes that can decay with their activity in Bq, `time_passed` in s since
+    /// the simulation started at `start_date`.
+    /// Without a source activity the material has its natural activity at the start.
+    pub fn activities(
+        &self,
+        global_transform: &GlobalTransform,
+        start_date: Date,
+        time_passed: f32,
+    ) -> Vec<(&Isotope, f32)> {
+        let isotopes = self.material.radioactive_isotopes();
+        match &self.source_activity {
+            Some(source_activity) => {
+                source_activity.isotope_activities(&isotopes, start_date, time_passed)
+            }
+            None => {
+                let weight = self.geometry.volume(global_transform) * self.material.density();
+                isotopes
+                    .into_iter()
+                    .map(|(isotope, activity)| {
+                        (
+                            isotope,
+                            activity * weight * decay_factor(isotope, time_passed),
+                        )
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// in kg
+    pub fn region_mass(&self, region: usize, global_transform: &GlobalTransform) -> f32 {
+        match &self.geometry {
+            Geometry::Voxels(grid) => {
+                let scale = global_transform.affine().matrix3.determinant().abs();
+                grid.regions()[region]
+                    .material
+                    .as_ref()
+                    .map_or(0.0, |material| {
+                        grid.region_volume(region) * scale * material.density()
+                    })
+            }
+            _ => 0.0,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Default, Component, Reflect)]
@@ -46,6 +304,245 @@ pub struct LinearSpawner {
     pub particle_energy: f32,
 }
 
+/// Spectra of detectors have this many energy bins per decade, from 1 eV to 10 GeV
+pub const SPECTRUM_BINS_PER_DECADE: usize = 10;
+pub const SPECTRUM_BINS: usize = 10 * SPECTRUM_BINS_PER_DECADE;
+/// bins of the cosine between the particle direction and the local x axis of a detector
+pub const ANGLE_BINS: usize = 20;
+/// older crossings are dropped from the list, the accumulators keep counting
+const MAX_RECORDED_CROSSINGS: usize = 10_000;
+
+/// Bin of a particle energy in eV in a detector spectrum
+pub fn spectrum_bin(energy: f32) -> usize {
+    ((energy.max(1.0).log10() * SPECTRUM_BINS_PER_DECADE as f32) as usize).min(SPECTRUM_BINS - 1)
+}
+
+/// Lower edge of a spectrum bin in eV
+pub fn spectrum_bin_energy(bin: usize) -> f32 {
+    10f32.powf(bin as f32 / SPECTRUM_BINS_PER_DECADE as f32)
+}
+
+/// Virtual detector that records the particles crossing its surface without affecting them,
+/// independent of any `Object` on the same entity
+#[derive(Debug, Clone, Default, Component)]
+pub struct SurfaceDetector {
+    pub surface: DetectorSurface,
+    /// most recent crossings, oldest first
+    pub crossings: VecDeque<Crossing>,
+    tallies: HashMap<ParticleType, CrossingTally>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub enum DetectorSurface {
+    /// local x = 0 plane bounded by the unit square, its normal is the local x axis
+    #[default]
+    Plane,
+    /// surface of a geometry in local space
+    Boundary(Geometry),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Crossing {
+    pub particle_type: ParticleType,
+    /// in eV
+    pub energy: f32,
+    /// normalized, in world space
+    pub direction: Vec3,
+    /// in s since the simulation started
+    pub time: f32,
+    /// along the normal of a plane or into a boundary
+    pub inward: bool,
+}
+
+/// Accumulated crossings of one particle type
+#[derive(Debug, Clone)]
+pub struct CrossingTally {
+    pub inward: u64,
+    pub outward: u64,
+    /// sum of 1/|cos| of the crossing angles of a plane, or the track length in m
+    /// inside a boundary
+    fluence_sum: f32,
+    /// crossings per `spectrum_bin`
+    pub energy_spectrum: Vec<u64>,
+    /// crossings per bin of the cosine to the local x axis, from -1 to 1
+    pub angle_spectrum: Vec<u64>,
+}
+
+impl Default for CrossingTally {
+    fn default() -> Self {
+        Self {
+            inward: 0,
+            outward: 0,
+            fluence_sum: 0.0,
+            energy_spectrum: vec![0; SPECTRUM_BINS],
+            angle_spectrum: vec![0; ANGLE_BINS],
+        }
+    }
+}
+
+impl SurfaceDetector {
+    pub fn new(surface: DetectorSurface) -> Self {
+        Self {
+            surface,
+            ..Default::default()
+        }
+    }
+
+    pub fn tally(&self, particle_type: ParticleType) -> Option<&CrossingTally> {
+        self.tallies.get(&particle_type)
+    }
+
+    /// Crossings along the normal or into the boundary minus the ones in the opposite direction
+    pub fn net_current(&self, particle_type: ParticleType) -> i64 {
+        self.tally(particle_type)
+            .map_or(0, |tally| tally.inward as i64 - tally.outward as i64)
+    }
+
+    /// in 1/m2, for a plane from the crossing angles, for a boundary from the track length
+    /// inside its volume
+    pub fn fluence(&self, particle_type: ParticleType, global_transform: &GlobalTransform) -> f32 {
+        let Some(tally) = self.tally(particle_type) else {
+            return 0.0;
+        };
+        let size = match &self.surface {
+            DetectorSurface::Plane => {
+                let scale = global_transform.compute_transform().scale.abs();
+                scale.y * scale.z
+            }
+            DetectorSurface::Boundary(geometry) => geometry.volume(global_transform),
+        };
+        if size > 0.0 {
+            tally.fluence_sum / size
+        } else {
+            0.0
+        }
+    }
+
+    /// `cos_axis` is the cosine between the direction and the local x axis in world space
+    fn record(&mut self, crossing: Crossing, cos_axis: f32) {
+        let tally = self.tallies.entry(crossing.particle_type).or_default();
+        if crossing.inward {
+            tally.inward += 1;
+        } else {
+            tally.outward += 1;
+        }
+        if matches!(self.surface, DetectorSurface::Plane) {
+            tally.fluence_sum += 1.0 / cos_axis.abs().max(0.01);
+        }
+        tally.energy_spectrum[spectrum_bin(crossing.energy)] += 1;
+        tally.angle_spectrum
+            [(((cos_axis + 1.0) / 2.0 * ANGLE_BINS as f32) as usize).min(ANGLE_BINS - 1)] += 1;
+
+        if self.crossings.len() >= MAX_RECORDED_CROSSINGS {
+            self.crossings.pop_front();
+        }
+        self.crossings.push_back(crossing);
+    }
+
+    pub fn clear(&mut self) {
+        self.crossings.clear();
+        self.tallies.clear();
+    }
+}
+
+/// energy at which the resolution of a `PulseHeightDetector` is given, the Cs-137 line in eV
+pub const RESOLUTION_REFERENCE_ENERGY: f32 = 661_657.0;
+
+/// Spectrometer made from the `Object` on the same entity, sums the energy deposited in it
+/// per primary history and counts the sums in a pulse-height spectrum
+#[derive(Debug, Clone, Component)]
+pub struct PulseHeightDetector {
+    /// relative FWHM of the Gaussian energy resolution at `RESOLUTION_REFERENCE_ENERGY`,
+    /// growing with the square root of the energy, `None` for an ideal detector
+    pub resolution: Option<f32>,
+    /// upper end of the spectrum in eV
+    pub max_energy: f32,
+    /// counts per channel, the channels split 0 to `max_energy` evenly
+    pub spectrum: Vec<u64>,
+    /// energy in eV deposited by histories that still have particles
+    pending: HashMap<u64, f32>,
+}
+
+impl PulseHeightDetector {
+    pub fn new(channels: usize, max_energy: f32, resolution: Option<f32>) -> Self {
+        Self {
+            resolution,
+            max_energy,
+            spectrum: vec![0; channels.max(1)],
+            pending: HashMap::new(),
+        }
+    }
+
+    /// `energy` in eV deposited by a particle of `history`
+    pub fn add(&mut self, history: u64, energy: f32) {
+        *self.pending.entry(history).or_default() += energy;
+    }
+
+    /// Count the histories without particles left
+    fn finish(&mut self, live_histories: &HashSet<u64>) {
+        let finished = self
+            .pending
+            .keys()
+            .filter(|history| !live_histories.contains(history))
+            .copied()
+            .collect::<Vec<_>>();
+        for history in finished {
+            let energy = self.pending.remove(&history).unwrap();
+            self.count(energy);
+        }
+    }
+
+    fn count(&mut self, energy: f32) {
+        if energy <= 0.0 {
+            return;
+        }
+        let energy = match self.resolution {
+            Some(resolution) => {
+                // FWHM = 2.355 sigma
+                let sigma = resolution * (energy * RESOLUTION_REFERENCE_ENERGY).sqrt() / 2.355;
+                let normal = (-2.0 * random_open().ln()).sqrt()
+                    * (std::f32::consts::TAU * fastrand::f32()).cos();
+                energy + sigma * normal
+            }
+            None => energy,
+        };
+        let channel = (energy / self.channel_width()).floor();
+        if channel >= 0.0 && (channel as usize) < self.spectrum.len() {
+            self.spectrum[channel as usize] += 1;
+        }
+    }
+
+    /// in eV
+    pub fn channel_width(&self) -> f32 {
+        self.max_energy / self.spectrum.len() as f32
+    }
+
+    /// Start a new spectrum with `channels`
+    pub fn set_channels(&mut self, channels: usize) {
+        self.spectrum = vec![0; channels.max(1)];
+    }
+
+    pub fn clear(&mut self) {
+        self.spectrum.iter_mut().for_each(|count| *count = 0);
+        self.pending.clear();
+    }
+}
+
+/// `SurfaceDetector` data collected from many threads while particles are processed
+#[derive(Default)]
+struct AtomicDetectorData {
+    crossings: Mutex<Vec<(Crossing, f32)>>,
+    /// in m, per particle type inside a boundary
+    track_lengths: [AtomicF32; PARTICLE_TYPES.len()],
+}
+
+fn particle_type_index(particle_type: ParticleType) -> usize {
+    PARTICLE_TYPES
+        .iter()
+        .position(|other| *other == particle_type)
+        .unwrap()
+}
+
 #[derive(Debug, Clone, PartialEq, Default, Component, Reflect)]
 #[reflect(Component)]
 pub struct Velocity(Vec3);
@@ -57,6 +554,8 @@ pub struct TimeData {
     pub multi_step: usize,
     pub halted: bool,
     pub time_passed: f32,
+    /// date the simulation started, source activities decay from their reference date to it
+    pub start_date: Date,
 }
 
 pub struct RadiationSimParticle;
@@ -71,14 +570,22 @@ impl Plugin for RadiationSimParticle {
                 multi_step: 16,
                 halted: false,
                 time_passed: 0.0,
+                start_date: Date::today(),
             })
             .add_event::<ResetParticles>()
             .add_startup_system(setup)
             .add_system(tick_time)
             .add_system(spawn_object_particles.in_set(OnUpdate(CurrentEnv::Sandbox)))
             .add_system(spawn_linear_particles.in_set(OnUpdate(CurrentEnv::Experiment)))
+            .add_system(
+                finish_pulse_heights
+                    .before(spawn_object_particles)
+                    .before(spawn_linear_particles)
+                    .before(process_particles),
+            )
             .add_system(reset_particles)
-            .add_system(process_particles);
+            .add_system(process_particles)
+            .add_system(refresh_materials);
     }
 }
 
@@ -94,6 +601,36 @@ fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
     ));
 }
 
+/// Point all materials to the reloaded substances
+fn refresh_materials(
+    mut reloaded: EventReader<SubstanceDataReloaded>,
+    substance_data: Res<SubstanceData>,
+    mut objects: Query<&mut Object>,
+    mut ambient_materials: Query<&mut AmbientMaterial>,
+    mut scene_geometries: Query<&mut SceneGeometry>,
+) {
+    if reloaded.iter().count() == 0 {
+        return;
+    }
+
+    for mut object in &mut objects {
+        object.material.refresh(&substance_data);
+        if let Geometry::Voxels(grid) = &mut object.geometry {
+            for region in Arc::make_mut(grid).regions_mut() {
+                if let Some(material) = &mut region.material {
+                    material.refresh(&substance_data);
+                }
+            }
+        }
+    }
+    for mut ambient_material in &mut ambient_materials {
+        ambient_material.material.refresh(&substance_data);
+    }
+    for mut scene_geometry in &mut scene_geometries {
+        scene_geometry.material.refresh(&substance_data);
+    }
+}
+
 fn tick_time(mut time_data: ResMut<TimeData>) {
     if time_data.halted {
         return;
@@ -102,94 +639,435 @@ fn tick_time(mut time_data: ResMut<TimeData>) {
     time_data.time_passed += time_data.time_step_calc * time_data.multi_step as f32;
 }
 
+/// The whole part of `expected` plus one with the probability of the fractional part
+fn random_count(expected: f32) -> usize {
+    expected.floor() as usize
+        + if (expected - expected.floor()) > fastrand::f32() {
+            1
+        } else {
+            0
+        }
+}
+
+/// A random item, the chance of each is proportional to its weight
+fn pick_weighted<T>(items: &[T], weight: impl Fn(&T) -> f32) -> &T {
+    let total: f32 = items.iter().map(&weight).sum();
+    let mut pick = fastrand::f32() * total;
+    items
+        .iter()
+        .find(|item| {
+            pick -= weight(item);
+            pick <= 0.0
+        })
+        .unwrap_or(&items[items.len() - 1])
+}
+
+fn random_direction() -> Vec3 {
+    Vec3::new(
+        fastrand::f32() - 0.5,
+        fastrand::f32() - 0.5,
+        fastrand::f32() - 0.5,
+    )
+    .normalize()
+}
+
+/// kinetic energy of both fission fragments in eV
+const FISSION_FRAGMENT_ENERGY: f32 = 170e6;
+/// mean number of prompt neutrons per fission
+const FISSION_NEUTRONS: f32 = 2.5;
+/// temperature of the Maxwell spectrum of the fission neutrons in eV
+const FISSION_NEUTRON_TEMPERATURE: f32 = 1.42e6;
+/// mean number of prompt gammas per fission
+const FISSION_GAMMAS: f32 = 7.0;
+/// mean energy of the prompt fission gammas in eV
+const FISSION_GAMMA_ENERGY: f32 = 0.9e6;
+/// typical energy of beta delayed neutrons in eV
+const DELAYED_NEUTRON_ENERGY: f32 = 0.5e6;
+/// typical energy of beta delayed protons in eV
+const DELAYED_PROTON_ENERGY: f32 = 2.0e6;
+
+/// A random number in (0, 1] that can be put into a logarithm
+fn random_open() -> f32 {
+    1.0 - fastrand::f32()
+}
+
+/// Energy of a fission neutron in eV from the Maxwell spectrum
+fn fission_neutron_energy() -> f32 {
+    let cos = (std::f32::consts::FRAC_PI_2 * fastrand::f32()).cos();
+    -FISSION_NEUTRON_TEMPERATURE * (random_open().ln() + random_open().ln() * cos * cos)
+}
+
+/// Spawn a particle with `energy` in eV flying in a random direction
+fn spawn_particle(
+    commands: &mut Commands,
+    position: Vec3,
+    particle_type: ParticleType,
+    energy: f32,
+    generation: Generation,
+    history: u64,
+) {
+    spawn_particle_towards(
+        commands,
+        position,
+        random_direction(),
+        particle_type,
+        energy,
+        generation,
+        history,
+    );
+}
+
+/// Spawn a particle with `energy` in eV flying in `direction`
+fn spawn_particle_towards(
+    commands: &mut Commands,
+    position: Vec3,
+    direction: Vec3,
+    particle_type: ParticleType,
+    energy: f32,
+    generation: Generation,
+    history: u64,
+) {
+    if energy <= 0.1 {
+        return;
+    }
+    match particle_type {
+        ParticleType::Gamma => commands.spawn((
+            TransformBundle::from_transform(Transform::from_translation(position)),
+            Particle {
+                energy,
+                particle_type,
+                generation,
+                history,
+            },
+            Velocity(direction * LIGHT_SPEED as f32),
+            VisibilityBundle::default(),
+        )),
+        _ => commands.spawn((
+            TransformBundle::from_transform(Transform::from_translation(position)),
+            Particle {
+                // these have energy as velocity
+                energy: 1.0,
+                particle_type,
+                generation,
+                history,
+            },
+            Velocity(direction * energy_to_velocity(energy, particle_type)),
+            VisibilityBundle::default(),
+        )),
+    };
+}
+
+/// in eV
+fn electron_rest_energy() -> f32 {
+    (*ELECTRON_MASS * LIGHT_SPEED_SQ / *EV_CONVERSION) as f32
+}
+
+/// Energy in eV and cosine of the scattering angle of a photon with `energy` in eV
+/// after Compton scattering, sampled from the Klein–Nishina distribution
+fn compton_scatter(energy: f32) -> (f32, f32) {
+    let k = energy / electron_rest_energy();
+    let eps0 = 1.0 / (1.0 + 2.0 * k);
+    let eps0_sq = eps0 * eps0;
+    let alpha1 = -eps0.ln();
+    let alpha2 = alpha1 + 0.5 * (1.0 - eps0_sq);
+
+    loop {
+        let (eps, eps_sq) = if alpha1 > alpha2 * fastrand::f32() {
+            let eps = (-alpha1 * fastrand::f32()).exp();
+            (eps, eps * eps)
+        } else {
+            let eps_sq = eps0_sq + (1.0 - eps0_sq) * fastrand::f32();
+            (eps_sq.sqrt(), eps_sq)
+        };
+        let one_minus_cos = (1.0 - eps) / (eps * k);
+        let sin_sq = one_minus_cos * (2.0 - one_minus_cos);
+        if 1.0 - eps * sin_sq / (1.0 + eps_sq) >= fastrand::f32() {
+            return (eps * energy, 1.0 - one_minus_cos);
+        }
+    }
+}
+
+/// `direction` turned by the angle with `cos` around a random axis
+fn scatter_direction(direction: Vec3, cos: f32) -> Vec3 {
+    let sin = (1.0 - cos * cos).max(0.0).sqrt();
+    let phi = std::f32::consts::TAU * fastrand::f32();
+    let (first, second) = direction.any_orthonormal_pair();
+    (direction * cos + (first * phi.cos() + second * phi.sin()) * sin).normalize()
+}
+
+/// Interaction of a photon with `energy` in eV with an atom of the material. Returns the
+/// energy deposited at the point of interaction, the energy and direction of the photon
+/// afterwards and spawns the particles leaving the point: X-rays and Auger electrons after
+/// photoelectric absorption and annihilation photons after pair production.
+fn photon_interaction(
+    material: &MaterialData,
+    particle: &Particle,
+    direction: Vec3,
+    position: Vec3,
+    par_commands: &ParallelCommands,
+) -> (f32, f32, Vec3) {
+    let energy = particle.energy;
+    // without a known composition the photon is absorbed
+    let Some(z) = material.pick_element() else {
+        return (energy, 0.0, direction);
+    };
+
+    let mut emitted = Vec::new();
+    let result = match pick_interaction(z as f64, energy as f64 / 1_000_000.0) {
+        PhotonInteraction::Photoelectric => {
+            let shells = AtomicShells::new(z);
+            if let Some(shell) = shells.photoelectric_shell(energy) {
+                emitted = shells
+                    .relax(shell)
+                    .into_iter()
+                    .map(|(particle_type, energy)| (particle_type, energy, random_direction()))
+                    .collect();
+            }
+            let escaping: f32 = emitted.iter().map(|(_, energy, _)| energy).sum();
+            (energy - escaping, 0.0, direction)
+        }
+        // the Compton electron stops right away
+        PhotonInteraction::Compton => {
+            let (scattered_energy, cos) = compton_scatter(energy);
+            (
+                energy - scattered_energy,
+                scattered_energy,
+                scatter_direction(direction, cos),
+            )
+        }
+        // electron and positron stop right away, the positron annihilates at rest
+        PhotonInteraction::Pair => {
+            let annihilation_direction = random_direction();
+            emitted.push((
+                ParticleType::Gamma,
+                electron_rest_energy(),
+                annihilation_direction,
+            ));
+            emitted.push((
+                ParticleType::Gamma,
+                electron_rest_energy(),
+                -annihilation_direction,
+            ));
+            (energy - 2.0 * electron_rest_energy(), 0.0, direction)
+        }
+    };
+
+    if !emitted.is_empty() {
+        par_commands.command_scope(|mut commands| {
+            for (particle_type, energy, direction) in emitted {
+                spawn_particle_towards(
+                    &mut commands,
+                    position,
+                    direction,
+                    particle_type,
+                    energy,
+                    Generation::Secondary,
+                    particle.history,
+                );
+            }
+        });
+    }
+    result
+}
+
 fn spawn_object_particles(
     time_data: ResMut<TimeData>,
-    query: Query<(&Transform, &GlobalTransform, &Object)>,
+    mut query: Query<(
+        &GlobalTransform,
+        &mut Object,
+        Option<&mut PulseHeightDetector>,
+    )>,
+    mesh_query: Query<(&DoseMesh, &GlobalTransform)>,
     mut commands: Commands,
 ) {
     if time_data.halted {
         return;
     }
 
-    for (transform, global_transform, object) in query.iter() {
-        let substance = object.material.pick_substance();
+    for (global_transform, mut object, mut pulse_heights) in &mut query {
+        let isotopes = object.activities(
+            global_transform,
+            time_data.start_date,
+            time_data.time_passed,
+        );
+        // in Bq
+        let activity: f32 = isotopes.iter().map(|(_, activity)| activity).sum();
+        if activity <= 0.0 {
+            continue;
+        }
+
+        // fission fragments and clusters stop right where they are created,
+        // their energy is deposited without spawning them
+        let mut local_deposits = Vec::new();
+        // positrons are spawned as electrons, which stop the same way,
+        // and annihilate at the decay position
+        let mut annihilations = Vec::new();
 
         for _ in 0..time_data.multi_step {
-            match &substance {
-                Substance::Element(element, n) => {
-                    if element.isotopes[n].is_usable {
-                        let volume = transform.scale.x * transform.scale.y * transform.scale.z;
-                        let weight = volume * element.density;
-                        let estimated_decays = element.isotopes[n].activity.unwrap()
-                            * weight
-                            * time_data.time_step_calc;
-
-                        let decays = estimated_decays.floor() as usize
-                            + if (estimated_decays - estimated_decays.floor()) > fastrand::f32() {
-                                1
-                            } else {
-                                0
-                            };
-
-                        for _ in 0..decays {
-                            let velocity_direction = Vec3::new(
-                                fastrand::f32() - 0.5,
-                                fastrand::f32() - 0.5,
-                                fastrand::f32() - 0.5,
-                            )
-                            .normalize();
-
-                            let pos_offset = Vec3::new(
-                                transform.scale.x * (fastrand::f32() - 0.5),
-                                transform.scale.y * (fastrand::f32() - 0.5),
-                                transform.scale.z * (fastrand::f32() - 0.5),
+            let decays = random_count(activity * time_data.time_step_calc);
+
+            for _ in 0..decays {
+                let local_position = object.geometry.random_point();
+                let position = global_transform.transform_point(local_position);
+
+                // pick the decaying isotope by its share of the activity
+                let (isotope, _) = pick_weighted(&isotopes, |(_, activity)| *activity);
+                let decay = pick_weighted(&isotope.decays, |decay| decay.intensity);
+                let energy = decay.decay_energy;
+
+                let history = new_history();
+                let mut spawn = |particle_type, energy| {
+                    spawn_particle(
+                        &mut commands,
+                        position,
+                        particle_type,
+                        energy,
+                        Generation::Primary,
+                        history,
+                    )
+                };
+
+                match decay.decay_type {
+                    DecayType::Alpha | DecayType::BetaDelayedAlpha => {
+                        spawn(ParticleType::Alpha, energy)
+                    }
+                    DecayType::BetaMinus => spawn(ParticleType::Electron, energy),
+                    // only branches listed as positron emission, with the positron energy
+                    DecayType::BetaPlus => {
+                        spawn(ParticleType::Electron, energy);
+                        annihilations.push((position, history));
+                    }
+                    // the decay energy of EC+B+ is the Q value of the capture, the branch is
+                    // taken as electron capture. The neutrino carries the energy,
+                    // the daughter atom relaxes with X-rays and Auger electrons
+                    DecayType::BetaElectronCapture | DecayType::ElectronCapture => {
+                        let shells = AtomicShells::new(isotope.z.saturating_sub(1));
+                        for (particle_type, energy) in shells.relax(shells.capture_shell(energy)) {
+                            spawn(particle_type, energy);
+                        }
+                    }
+                    DecayType::DoubleBeta => {
+                        spawn(ParticleType::Electron, energy / 2.0);
+                        spawn(ParticleType::Electron, energy / 2.0);
+                    }
+                    // the transition is only emitted as gamma if no lines are known
+                    DecayType::IsomericTransition => {
+                        if isotope.photon_lines.is_empty() {
+                            spawn(ParticleType::Gamma, energy)
+                        }
+                    }
+                    DecayType::BetaDelayedNeutrons(count) => {
+                        spawn(ParticleType::Electron, energy);
+                        for _ in 0..count {
+                            spawn(ParticleType::Neutron, DELAYED_NEUTRON_ENERGY);
+                        }
+                    }
+                    DecayType::BetaDelayedProtons(count) => {
+                        spawn(ParticleType::Electron, energy);
+                        for _ in 0..count {
+                            spawn(ParticleType::Proton, DELAYED_PROTON_ENERGY);
+                        }
+                    }
+                    DecayType::Protons(count) => {
+                        for _ in 0..count {
+                            spawn(ParticleType::Proton, energy / count as f32);
+                        }
+                    }
+                    DecayType::Neutrons(count) => {
+                        for _ in 0..count {
+                            spawn(ParticleType::Neutron, energy / count as f32);
+                        }
+                    }
+                    DecayType::SpontaneousFission => {
+                        local_deposits.push((local_position, FISSION_FRAGMENT_ENERGY, history));
+                        for _ in 0..random_count(FISSION_NEUTRONS) {
+                            spawn(ParticleType::Neutron, fission_neutron_energy());
+                        }
+                        for _ in 0..random_count(FISSION_GAMMAS) {
+                            spawn(
+                                ParticleType::Gamma,
+                                -FISSION_GAMMA_ENERGY * random_open().ln(),
                             );
+                        }
+                    }
+                    DecayType::ClusterEmission => {
+                        local_deposits.push((local_position, energy, history))
+                    }
+                    // reported as a data warning when the isotope is loaded
+                    DecayType::Other => {}
+                }
 
-                            let decay = &element.isotopes[n].decays[0];
-
-                            let particle_type = match decay.decay_type {
-                                element::DecayType::Alpha => ParticleType::Alpha,
-                                element::DecayType::BetaElectronCapture => ParticleType::Electron,
-                                element::DecayType::BetaMinus => ParticleType::Electron,
-                                element::DecayType::BetaPlus => ParticleType::Electron,
-                                _ => panic!("incorrect decay type"),
-                            };
-
-                            // spawn particle
-                            commands.spawn((
-                                TransformBundle::from_transform(Transform::from_translation(
-                                    global_transform.translation() + pos_offset,
-                                )),
-                                Particle {
-                                    // these have energy as velocity
-                                    energy: 1.0,
-                                    particle_type,
-                                },
-                                Velocity(
-                                    velocity_direction
-                                        * energy_to_velocity(decay.decay_energy, particle_type),
-                                ),
-                                VisibilityBundle::default(),
-                            ));
-
-                            // spawn gamma ray
-                            if let Some(gamma_energy) = decay.gamma_energy {
-                                commands.spawn((
-                                    TransformBundle::from_transform(Transform::from_translation(
-                                        transform.translation + pos_offset,
-                                    )),
-                                    Particle {
-                                        energy: gamma_energy,
-                                        particle_type: ParticleType::Gamma,
-                                    },
-                                    Velocity(velocity_direction * LIGHT_SPEED as f32),
-                                    VisibilityBundle::default(),
-                                ));
+                // spawn gamma and X-rays, each line with its intensity,
+                // converted transitions emit an electron instead of the photon
+                for line in &isotope.photon_lines {
+                    let transitions = 1.0 + line.conversion_coefficient;
+                    for _ in 0..random_count(line.intensity * transitions) {
+                        if fastrand::f32() * transitions >= line.conversion_coefficient {
+                            spawn(ParticleType::Gamma, line.energy);
+                            continue;
+                        }
+                        let shells = AtomicShells::new(line.daughter_z);
+                        let shell = shells.conversion_shell(line.energy);
+                        spawn(
+                            ParticleType::Electron,
+                            line.energy - shells.binding_energy(shell),
+                        );
+                        // listed X-rays already include the relaxation after conversion
+                        if !isotope
+                            .photon_lines
+                            .iter()
+                            .any(|line| line.photon_type == PhotonType::XRay)
+                        {
+                            for (particle_type, energy) in shells.relax(shell) {
+                                spawn(particle_type, energy);
                             }
                         }
                     }
                 }
-                _ => {}
+            }
+        }
+
+        for (position, history) in annihilations {
+            let direction = random_direction();
+            for direction in [direction, -direction] {
+                spawn_particle_towards(
+                    &mut commands,
+                    position,
+                    direction,
+                    ParticleType::Gamma,
+                    electron_rest_energy(),
+                    Generation::Secondary,
+                    history,
+                );
+            }
+        }
+
+        for (local_position, energy, history) in local_deposits {
+            // heavy fragments are weighted like alphas for the equivalent dose
+            let fragment = Particle {
+                particle_type: ParticleType::Alpha,
+                energy: 0.0,
+                generation: Generation::Primary,
+                history,
+            };
+            object.absorbed_energy.add(&fragment, energy, energy);
+            if let Some(pulse_heights) = &mut pulse_heights {
+                pulse_heights.add(history, energy);
+            }
+            if let Some(region) = object.geometry.region_at(local_position) {
+                if let Some(region_energy) = object.region_absorbed_energy.get_mut(region) {
+                    region_energy.add(&fragment, energy, energy);
+                }
+            }
+
+            let position = global_transform.transform_point(local_position);
+            let density = object.material_at(local_position).density();
+            for (mesh, mesh_transform) in &mesh_query {
+                mesh.add(
+                    mesh_transform.affine().inverse().transform_point3(position),
+                    energy,
+                    density,
+                );
             }
         }
     }
@@ -212,14 +1090,7 @@ fn spawn_linear_particles(
                 (ParticleType::Gamma, spawner.gamma_rate),
             ];
             for (particle_type, rate) in particle_types {
-                let estimated_decays = rate * time_data.time_step_calc;
-
-                let decays = estimated_decays.floor() as usize
-                    + if (estimated_decays - estimated_decays.floor()) > fastrand::f32() {
-                        1
-                    } else {
-                        0
-                    };
+                let decays = random_count(rate * time_data.time_step_calc);
 
                 for _ in 0..decays {
                     let velocity_direction = Vec3::new(1.0, 0.0, 0.0);
@@ -239,6 +1110,8 @@ fn spawn_linear_particles(
                                 // these have energy as velocity
                                 energy: 1.0,
                                 particle_type,
+                                generation: Generation::Primary,
+                                history: new_history(),
                             },
                             Velocity(
                                 velocity_direction
@@ -254,6 +1127,8 @@ fn spawn_linear_particles(
                             Particle {
                                 energy: spawner.particle_energy,
                                 particle_type: ParticleType::Gamma,
+                                generation: Generation::Primary,
+                                history: new_history(),
                             },
                             Velocity(velocity_direction * LIGHT_SPEED as f32),
                             VisibilityBundle::default(),
@@ -271,6 +1146,9 @@ pub struct ResetParticles;
 fn reset_particles(
     particle_query: Query<Entity, With<Particle>>,
     mut object_query: Query<&mut Object>,
+    mesh_query: Query<&DoseMesh>,
+    mut detector_query: Query<&mut SurfaceDetector>,
+    mut pulse_height_query: Query<&mut PulseHeightDetector>,
     mut commands: Commands,
     mut events: EventReader<ResetParticles>,
 ) {
@@ -281,8 +1159,40 @@ fn reset_particles(
         });
 
         object_query.iter_mut().for_each(|mut object| {
-            object.absorbed_energy = 0.0;
+            object.absorbed_energy.clear();
+            object
+                .region_absorbed_energy
+                .iter_mut()
+                .for_each(EnergyDeposits::clear);
         });
+        mesh_query.iter().for_each(DoseMesh::clear);
+        detector_query
+            .iter_mut()
+            .for_each(|mut detector| detector.clear());
+        pulse_height_query
+            .iter_mut()
+            .for_each(|mut detector| detector.clear());
+    }
+}
+
+/// Count the pulse heights of the histories that ended with the last update
+fn finish_pulse_heights(
+    particle_query: Query<&Particle>,
+    mut detector_query: Query<&mut PulseHeightDetector>,
+) {
+    if detector_query
+        .iter()
+        .all(|detector| detector.pending.is_empty())
+    {
+        return;
+    }
+
+    let live_histories = particle_query
+        .iter()
+        .map(|particle| particle.history)
+        .collect::<HashSet<_>>();
+    for mut detector in &mut detector_query {
+        detector.finish(&live_histories);
     }
 }
 
@@ -291,7 +1201,16 @@ fn process_particles(
 
     ambient_query: Query<&AmbientMaterial>,
     mut query: Query<(Entity, &mut Transform, &mut Velocity, &mut Particle), Without<Object>>,
-    mut object_query: Query<(&mut Object, &Transform, &GlobalTransform), Without<Particle>>,
+    mut object_query: Query<
+        (
+            &mut Object,
+            &GlobalTransform,
+            Option<&mut PulseHeightDetector>,
+        ),
+        Without<Particle>,
+    >,
+    mesh_query: Query<(&DoseMesh, &GlobalTransform)>,
+    mut detector_query: Query<(&mut SurfaceDetector, &GlobalTransform)>,
 
     par_commands: ParallelCommands,
 ) {
@@ -303,7 +1222,40 @@ fn process_particles(
 
     let objects = object_query
         .iter_mut()
-        .map(|q| (q, AtomicF32::new(0.0)))
+        .map(|(object, global_transform, pulse_heights)| {
+            let world_to_local = global_transform.affine().inverse();
+            let region_energies = object
+                .geometry
+                .regions()
+                .iter()
+                .map(|_| AtomicEnergyDeposits::default())
+                .collect::<Vec<_>>();
+            // deposits per history for spectrometers
+            let pulse_heights = pulse_heights.map(|detector| (detector, Mutex::new(Vec::new())));
+            (
+                (object, world_to_local, pulse_heights),
+                AtomicEnergyDeposits::default(),
+                region_energies,
+            )
+        })
+        .collect::<Vec<_>>();
+    let meshes = mesh_query
+        .iter()
+        .map(|(mesh, global_transform)| (mesh, global_transform.affine().inverse()))
+        .collect::<Vec<_>>();
+    let detectors = detector_query
+        .iter_mut()
+        .map(|(detector, global_transform)| {
+            let world_to_local = global_transform.affine().inverse();
+            // normal of the plane, the axis of the angle spectrum
+            let axis = global_transform.right();
+            (
+                detector,
+                world_to_local,
+                axis,
+                AtomicDetectorData::default(),
+            )
+        })
         .collect::<Vec<_>>();
 
     query
@@ -312,91 +1264,145 @@ fn process_particles(
             for _ in 0..time_data.multi_step {
                 // move particle
                 let move_step = velocity.0 * time_data.time_step_move;
+                let previous_position = transform.translation;
                 transform.translation += move_step;
 
                 // collide particle
 
-                let mut hit_substance = None;
+                let mut hit_material = &ambient_material.material;
                 let mut hit_obstacle = None;
 
-                for ((object, object_transform, object_global_transform), absorbed_energy) in
+                for ((object, world_to_local, pulse_heights), absorbed_energy, region_energies) in
                     &objects
                 {
-                    let par_pos = transform.translation;
-                    let obj_pos = object_global_transform.translation();
-                    let obj_scale = object_transform.scale;
+                    let local_pos = world_to_local.transform_point3(transform.translation);
 
                     // check for hit
-                    if par_pos.x > obj_pos.x - obj_scale.x / 2.0
-                        && par_pos.x < obj_pos.x + obj_scale.x / 2.0
-                        && par_pos.y > obj_pos.y - obj_scale.y / 2.0
-                        && par_pos.y < obj_pos.y + obj_scale.y / 2.0
-                        && par_pos.z > obj_pos.z - obj_scale.z / 2.0
-                        && par_pos.z < obj_pos.z + obj_scale.z / 2.0
-                    {
-                        let substance = object.material.pick_substance();
-
-                        hit_substance = Some(substance);
-                        hit_obstacle = Some(absorbed_energy);
+                    if object.geometry.contains(local_pos) {
+                        hit_material = object.material_at(local_pos);
+                        hit_obstacle = Some((
+                            absorbed_energy,
+                            object
+                                .geometry
+                                .region_at(local_pos)
+                                .map(|region| &region_energies[region]),
+                            pulse_heights.as_ref().map(|(_, deposits)| deposits),
+                        ));
                     }
                 }
 
-                if hit_substance.is_none() {
-                    hit_substance = Some(ambient_material.material.pick_substance());
-                }
-
-                // apply material
-                if let Some(substance) = hit_substance {
-                    if let Some(stopping_powers) = substance.stopping_powers(particle.particle_type)
-                    {
-                        let energy = match particle.particle_type {
-                            ParticleType::Gamma => particle.energy,
-                            _ => velocity_to_energy(velocity.0.length(), particle.particle_type),
-                        };
+                let energy = match particle.particle_type {
+                    ParticleType::Gamma => particle.energy,
+                    _ => velocity_to_energy(velocity.0.length(), particle.particle_type),
+                };
 
-                        // eV/m or 1/m
-                        let stopping_power = pick_stopping_power(stopping_powers, energy);
-
-                        let energy_transfer = match particle.particle_type {
-                            // gammas either are unaffected or completely gone
-                            ParticleType::Gamma => {
-                                if std::f32::consts::E
-                                    .powf(-1.0 * stopping_power * move_step.length())
-                                    < fastrand::f32()
-                                {
-                                    // transfer all energy if "hit"
-                                    energy
-                                } else {
-                                    // none if no "hit"
-                                    0.0
-                                }
+                // record detector crossings before the particle interacts
+                for (detector, world_to_local, axis, data) in &detectors {
+                    let previous = world_to_local.transform_point3(previous_position);
+                    let current = world_to_local.transform_point3(transform.translation);
+                    let inward = match &detector.surface {
+                        DetectorSurface::Plane => {
+                            let fraction = previous.x / (previous.x - current.x);
+                            let hit = previous.lerp(current, fraction);
+                            ((previous.x < 0.0) != (current.x < 0.0)
+                                && hit.y.abs() <= 0.5
+                                && hit.z.abs() <= 0.5)
+                                .then_some(current.x >= 0.0)
+                        }
+                        DetectorSurface::Boundary(geometry) => {
+                            let inside = geometry.contains(current);
+                            if inside {
+                                data.track_lengths[particle_type_index(particle.particle_type)]
+                                    .fetch_add(move_step.length(), Ordering::Relaxed);
                             }
-                            _ => stopping_power * move_step.length(),
-                        };
-
-                        // add to obstacle
-                        if let Some(absorbed_energy) = hit_obstacle {
-                            absorbed_energy.fetch_add(
-                                // account for equivalent dose
-                                match particle.particle_type {
-                                    ParticleType::Alpha => energy_transfer * 20.0,
-                                    _ => energy_transfer,
-                                },
-                                Ordering::Relaxed,
-                            );
+                            (geometry.contains(previous) != inside).then_some(inside)
                         }
+                    };
 
-                        let new_energy = (energy - energy_transfer).max(0.0);
+                    if let Some(inward) = inward {
+                        let direction = velocity.0.normalize_or_zero();
+                        let cos_axis = direction.dot(*axis);
+                        let crossing = Crossing {
+                            particle_type: particle.particle_type,
+                            energy,
+                            direction,
+                            time: time_data.time_passed,
+                            inward,
+                        };
+                        data.crossings.lock().unwrap().push((crossing, cos_axis));
+                    }
+                }
 
-                        match particle.particle_type {
-                            ParticleType::Gamma => {
-                                particle.energy = new_energy;
+                // apply material
+                // eV/m or 1/m
+                if let Some(stopping_power) =
+                    hit_material.stopping_power(particle.particle_type, energy)
+                {
+                    let (energy_transfer, new_energy) = match particle.particle_type {
+                        // gammas either pass unaffected or interact with an atom
+                        ParticleType::Gamma => {
+                            if std::f32::consts::E.powf(-stopping_power * move_step.length())
+                                < fastrand::f32()
+                            {
+                                let (energy_transfer, new_energy, direction) = photon_interaction(
+                                    hit_material,
+                                    &particle,
+                                    velocity.0.normalize(),
+                                    transform.translation,
+                                    &par_commands,
+                                );
+                                velocity.0 = direction * LIGHT_SPEED as f32;
+                                (energy_transfer, new_energy)
+                            } else {
+                                // none if no "hit"
+                                (0.0, energy)
                             }
-                            _ => {
-                                velocity.0 = velocity.0.normalize()
-                                    * energy_to_velocity(new_energy, particle.particle_type)
+                        }
+                        // neutrons pass unaffected or are removed, depositing all their energy
+                        ParticleType::Neutron => {
+                            if std::f32::consts::E.powf(-stopping_power * move_step.length())
+                                < fastrand::f32()
+                            {
+                                (energy, 0.0)
+                            } else {
+                                (0.0, energy)
                             }
                         }
+                        _ => {
+                            let energy_transfer = stopping_power * move_step.length();
+                            (energy_transfer, (energy - energy_transfer).max(0.0))
+                        }
+                    };
+
+                    // add to obstacle, weighting factors are applied when the dose is read
+                    if let Some((absorbed_energy, region_energy, pulse_heights)) = hit_obstacle {
+                        absorbed_energy.add(&particle, energy, energy_transfer);
+                        if let Some(region_energy) = region_energy {
+                            region_energy.add(&particle, energy, energy_transfer);
+                        }
+                        if let Some(pulse_heights) = pulse_heights {
+                            pulse_heights
+                                .lock()
+                                .unwrap()
+                                .push((particle.history, energy_transfer));
+                        }
+                    }
+                    for (mesh, world_to_local) in &meshes {
+                        mesh.add(
+                            world_to_local.transform_point3(transform.translation),
+                            energy_transfer,
+                            hit_material.density(),
+                        );
+                    }
+
+                    match particle.particle_type {
+                        ParticleType::Gamma => {
+                            particle.energy = new_energy;
+                        }
+                        _ => {
+                            velocity.0 = velocity.0.normalize()
+                                * energy_to_velocity(new_energy, particle.particle_type)
+                        }
                     }
                 }
 
@@ -409,23 +1415,50 @@ fn process_particles(
             }
         });
 
-    for ((mut obstacle, _, _), absorbed_energy) in objects {
-        obstacle.absorbed_energy += absorbed_energy.load(Ordering::Relaxed);
+    for ((mut obstacle, _, pulse_heights), absorbed_energy, region_energies) in objects {
+        absorbed_energy.add_to(&mut obstacle.absorbed_energy);
+        if let Some((mut detector, deposits)) = pulse_heights {
+            for (history, energy) in deposits.into_inner().unwrap() {
+                detector.add(history, energy);
+            }
+        }
+        obstacle
+            .region_absorbed_energy
+            .resize(region_energies.len(), EnergyDeposits::default());
+        for (energy, region_energy) in obstacle
+            .region_absorbed_energy
+            .iter_mut()
+            .zip(region_energies)
+        {
+            region_energy.add_to(energy);
+        }
     }
-}
 
-fn pick_stopping_power(stopping_powers: &StoppingPower, energy: f32) -> f32 {
-    for (stop_energy, stopping_power) in stopping_powers {
-        if *stop_energy > energy {
-            return *stopping_power;
+    for (mut detector, _, _, data) in detectors {
+        for (crossing, cos_axis) in data.crossings.into_inner().unwrap() {
+            detector.record(crossing, cos_axis);
+        }
+        if let DetectorSurface::Boundary(_) = detector.surface {
+            for particle_type in PARTICLE_TYPES {
+                let track_length =
+                    data.track_lengths[particle_type_index(particle_type)].load(Ordering::Relaxed);
+                if track_length > 0.0 {
+                    detector
+                        .tallies
+                        .entry(particle_type)
+                        .or_default()
+                        .fluence_sum += track_length;
+                }
+            }
         }
     }
-    return stopping_powers.last().unwrap().1;
 }
 
 fn energy_to_velocity(energy: f32, particle_type: ParticleType) -> f32 {
     let mass = match particle_type {
         ParticleType::Electron => *ELECTRON_MASS,
+        ParticleType::Proton => *PROTON_MASS,
+        ParticleType::Neutron => *NEUTRON_MASS,
         _ => *ALPHA_MASS,
     };
 
@@ -438,6 +1471,8 @@ fn energy_to_velocity(energy: f32, particle_type: ParticleType) -> f32 {
 fn velocity_to_energy(velocity: f32, particle_type: ParticleType) -> f32 {
     let mass = match particle_type {
         ParticleType::Electron => *ELECTRON_MASS,
+        ParticleType::Proton => *PROTON_MASS,
+        ParticleType::Neutron => *NEUTRON_MASS,
         _ => *ALPHA_MASS,
     };
 
@@ -445,3 +1480,23 @@ fn velocity_to_energy(velocity: f32, particle_type: ParticleType) -> f32 {
 
     ((k - 1.0) * mass * LIGHT_SPEED_SQ / *EV_CONVERSION) as f32
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates() {
+        let date = |year, month, day| Date { year, month, day };
+        assert_eq!(date(1970, 1, 1).days(), 0);
+        assert_eq!(date(2000, 3, 1).days(), 11_017);
+        assert_eq!(date(1969, 12, 31).days(), -1);
+        for days in [-800_000, -1, 0, 59, 11_016, 20_000, 800_000] {
+            assert_eq!(Date::from_days(days).days(), days);
+        }
+        assert_eq!(date(2024, 2, 10).days_in_month(), 29);
+        assert_eq!(date(2100, 2, 10).days_in_month(), 28);
+        assert_eq!(date(2023, 12, 31).days_in_month(), 31);
+        assert_eq!(date(2023, 12, 31).seconds_until(date(2024, 1, 1)), 86_400.0);
+    }
+}
@@ -0,0 +1,326 @@
+use std::sync::Arc;
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+
+use crate::{
+    AssetHandles, EnergyDeposits, Geometry, MaterialData, Object, SubstanceData, Tissue, VoxelGrid,
+    VoxelRegion,
+};
+
+pub struct RadiationSimPhantom;
+
+impl Plugin for RadiationSimPhantom {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<VoxelPhantom>()
+            .init_asset_loader::<VoxelPhantomLoader>()
+            .add_system(resolve_voxel_phantoms);
+    }
+}
+
+/// Voxelised phantom as read from a `.phantom` file.
+///
+/// The file starts with an ascii header, one entry per line:
+/// ```text
+/// # comment
+/// dimensions 64 64 128
+/// voxel_size 0.002 0.002 0.002
+/// format u8
+/// organ 0 - Outside
+/// organ 1 Water Soft Tissue
+/// tissue 1 remainder
+/// density 1 1040
+/// data
+/// ```
+/// `voxel_size` is in m, `format` is `u8` or `u16` (little endian) and each organ
+/// maps its id to a substance (compound name or element symbol, `-` for no material)
+/// and a name. `tissue` optionally tags a previously listed organ with a `Tissue` key
+/// for the effective dose and `density` overrides its density in kg/m3, for tissues that are
+/// approximated by a denser or lighter substance. The raw voxel ids follow directly after the `data` line, x changing fastest.
+#[derive(Debug, Clone, TypeUuid)]
+#[uuid = "5c1a3f0e-2b8d-4e77-9a61-0d3e8f6b2c41"]
+pub struct VoxelPhantom {
+    pub dimensions: UVec3,
+    /// in m
+    pub voxel_size: Vec3,
+    pub organs: Vec<PhantomOrgan>,
+    pub voxels: Vec<u16>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PhantomOrgan {
+    pub id: u16,
+    /// compound name or element symbol, `None` for voxels outside the phantom
+    pub substance: Option<String>,
+    pub name: String,
+    pub tissue: Option<Tissue>,
+    /// in kg/m3, replaces the density of the substance
+    pub density: Option<f32>,
+}
+
+#[derive(Debug)]
+pub enum PhantomError {
+    MissingData,
+    InvalidHeader(String),
+    UnknownOrgan(u16),
+    DataLength { expected: usize, found: usize },
+}
+
+impl std::fmt::Display for PhantomError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingData => f.write_str("phantom has no data section"),
+            Self::InvalidHeader(line) => f.write_fmt(format_args!("invalid header line: {}", line)),
+            Self::UnknownOrgan(id) => {
+                f.write_fmt(format_args!("voxel uses unknown organ id {}", id))
+            }
+            Self::DataLength { expected, found } => f.write_fmt(format_args!(
+                "expected {} bytes of voxel data, found {}",
+                expected, found
+            )),
+        }
+    }
+}
+
+impl std::error::Error for PhantomError {}
+
+impl VoxelPhantom {
+    pub fn parse(bytes: &[u8]) -> Result<Self, PhantomError> {
+        const DATA_MARKER: &[u8] = b"data\n";
+
+        let data_start = bytes
+            .windows(DATA_MARKER.len())
+            .position(|window| window == DATA_MARKER)
+            .ok_or(PhantomError::MissingData)?;
+        let header = String::from_utf8_lossy(&bytes[..data_start]);
+        let data = &bytes[data_start + DATA_MARKER.len()..];
+
+        let mut dimensions = None;
+        let mut voxel_size = None;
+        let mut bytes_per_voxel = 1;
+        let mut organs = Vec::new();
+
+        for line in header.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = || PhantomError::InvalidHeader(line.to_owned());
+            let mut parts = line.split_whitespace();
+            match parts.next() {
+                Some("dimensions") => {
+                    let values = parts
+                        .map(|v| v.parse::<u32>())
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|_| invalid())?;
+                    let [x, y, z] = values[..] else {
+                        return Err(invalid());
+                    };
+                    dimensions = Some(UVec3::new(x, y, z));
+                }
+                Some("voxel_size") => {
+                    let values = parts
+                        .map(|v| v.parse::<f32>())
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|_| invalid())?;
+                    let [x, y, z] = values[..] else {
+                        return Err(invalid());
+                    };
+                    voxel_size = Some(Vec3::new(x, y, z));
+                }
+                Some("format") => {
+                    bytes_per_voxel = match parts.next() {
+                        Some("u8") => 1,
+                        Some("u16") => 2,
+                        _ => return Err(invalid()),
+                    };
+                }
+                Some("organ") => {
+                    let id = parts
+                        .next()
+                        .and_then(|id| id.parse().ok())
+                        .ok_or_else(invalid)?;
+                    let substance = match parts.next().ok_or_else(invalid)? {
+                        "-" => None,
+                        substance => Some(substance.to_owned()),
+                    };
+                    organs.push(PhantomOrgan {
+                        id,
+                        substance,
+                        name: parts.collect::<Vec<_>>().join(" "),
+                        tissue: None,
+                        density: None,
+                    });
+                }
+                Some("tissue") => {
+                    let id = parts
+                        .next()
+                        .and_then(|id| id.parse::<u16>().ok())
+                        .ok_or_else(invalid)?;
+                    let tissue = parts
+                        .next()
+                        .and_then(Tissue::from_key)
+                        .ok_or_else(invalid)?;
+                    let organ = organs
+                        .iter_mut()
+                        .find(|organ| organ.id == id)
+                        .ok_or_else(invalid)?;
+                    organ.tissue = Some(tissue);
+                }
+                Some("density") => {
+                    let id = parts
+                        .next()
+                        .and_then(|id| id.parse::<u16>().ok())
+                        .ok_or_else(invalid)?;
+                    let density = parts
+                        .next()
+                        .and_then(|density| density.parse::<f32>().ok())
+                        .filter(|density| *density > 0.0)
+                        .ok_or_else(invalid)?;
+                    let organ = organs
+                        .iter_mut()
+                        .find(|organ| organ.id == id)
+                        .ok_or_else(invalid)?;
+                    organ.density = Some(density);
+                }
+                _ => return Err(invalid()),
+            }
+        }
+
+        let dimensions = dimensions.ok_or(PhantomError::InvalidHeader("dimensions".to_owned()))?;
+        let voxel_size = voxel_size.ok_or(PhantomError::InvalidHeader("voxel_size".to_owned()))?;
+
+        let expected = (dimensions.x * dimensions.y * dimensions.z) as usize * bytes_per_voxel;
+        if data.len() < expected {
+            return Err(PhantomError::DataLength {
+                expected,
+                found: data.len(),
+            });
+        }
+
+        let voxels = match bytes_per_voxel {
+            1 => data[..expected].iter().map(|v| *v as u16).collect(),
+            _ => data[..expected]
+                .chunks_exact(2)
+                .map(|v| u16::from_le_bytes([v[0], v[1]]))
+                .collect(),
+        };
+
+        Ok(Self {
+            dimensions,
+            voxel_size,
+            organs,
+            voxels,
+        })
+    }
+
+    /// Replace organ ids by indices into the organ list and look up the materials.
+    pub fn to_voxel_grid(&self, substance_data: &SubstanceData) -> Result<VoxelGrid, PhantomError> {
+        let mut id_to_region = vec![None; u16::MAX as usize + 1];
+        let regions = self
+            .organs
+            .iter()
+            .enumerate()
+            .map(|(i, organ)| {
+                id_to_region[organ.id as usize] = Some(i as u16);
+                VoxelRegion {
+                    id: organ.id,
+                    name: organ.name.clone(),
+                    material: organ.substance.as_ref().and_then(|name| {
+                        let substance = substance_data.find_absorber(name);
+                        if substance.is_none() {
+                            log::warn!("Unknown phantom substance {} for {}", name, organ.name);
+                        }
+                        substance.map(|substance| {
+                            let mut material = MaterialData::new(vec![(1.0, substance)]);
+                            material.set_density_override(organ.density);
+                            material
+                        })
+                    }),
+                    tissue: organ.tissue,
+                }
+            })
+            .collect();
+
+        let voxels = self
+            .voxels
+            .iter()
+            .map(|id| id_to_region[*id as usize].ok_or(PhantomError::UnknownOrgan(*id)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(VoxelGrid::new(
+            self.dimensions,
+            self.voxel_size,
+            voxels,
+            regions,
+        ))
+    }
+}
+
+#[derive(Default)]
+pub struct VoxelPhantomLoader;
+
+impl AssetLoader for VoxelPhantomLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let phantom = VoxelPhantom::parse(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(phantom));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["phantom"]
+    }
+}
+
+/// Becomes an `Object` with `Geometry::Voxels` once the phantom is loaded
+#[derive(Debug, Clone, Component)]
+pub struct VoxelPhantomSource(pub Handle<VoxelPhantom>);
+
+fn resolve_voxel_phantoms(
+    query: Query<(Entity, &VoxelPhantomSource)>,
+    phantoms: Res<Assets<VoxelPhantom>>,
+    substance_data: Res<SubstanceData>,
+    asset_handles: Res<AssetHandles>,
+    mut commands: Commands,
+) {
+    for (entity, source) in query.iter() {
+        let Some(phantom) = phantoms.get(&source.0) else {
+            continue;
+        };
+
+        commands.entity(entity).remove::<VoxelPhantomSource>();
+        match phantom.to_voxel_grid(&substance_data) {
+            Ok(grid) => {
+                let region_count = grid.regions().len();
+                let size = grid.size();
+                commands
+                    .entity(entity)
+                    .insert(Object {
+                        geometry: Geometry::Voxels(Arc::new(grid)),
+                        region_absorbed_energy: vec![EnergyDeposits::default(); region_count],
+                        ..Default::default()
+                    })
+                    .with_children(|parent| {
+                        // outline of the grid, the voxels themselves are not rendered
+                        parent.spawn(PbrBundle {
+                            material: asset_handles.light_grey_material.as_ref().unwrap().clone(),
+                            mesh: asset_handles.cube_mesh.as_ref().unwrap().clone(),
+                            transform: Transform::from_scale(size),
+                            ..Default::default()
+                        });
+                    });
+            }
+            Err(e) => log::error!("Could not create phantom: {}", e),
+        }
+    }
+}
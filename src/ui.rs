@@ -1,10 +1,15 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, utils::HashMap};
 use bevy_egui::{egui, EguiContexts, EguiPlugin};
 
 use crate::{
-    env::ExperimentTarget, material::MaterialData, particle::LinearSpawner, presets,
-    AmbientMaterial, AssetHandles, CurrentEnv, Human, HumanRoot, InterfaceState, Object, Particle,
-    ResetParticles, SandboxObject, SubstanceData, TimeData, EV_CONVERSION,
+    compound::parse_nuclide, effective_dose, env::ExperimentObject, env::ExperimentTarget,
+    material::MaterialData, organ_doses, particle::Date, particle::LinearSpawner, presets,
+    spectrum_bin_energy, ActivityUnit, AmbientMaterial, AssetHandles, CompoundError, CurrentEnv,
+    DetectorSurface, DoseMesh, Dosimetry, EnergyDeposits, Generation, GroupDose, GroupMember,
+    Human, HumanRoot, InterfaceState, Object, ObjectGroup, OrganDose, PartDose, Particle,
+    ParticleType, PulseHeightDetector, ResetParticles, SandboxObject, Slice, SourceActivity,
+    Substance, SubstanceData, SurfaceDetector, TimeData, VoxelPhantomSource, WeightingModel,
+    ANGLE_BINS, PARTICLE_TYPES, SPECTRUM_BINS_PER_DECADE,
 };
 
 pub struct RadiationSimUI;
@@ -14,14 +19,20 @@ impl Plugin for RadiationSimUI {
         app.add_plugin(EguiPlugin)
             .add_system(render_main_ui)
             .add_system(render_legend)
-            .add_system(render_object_editor);
+            .add_system(render_data_warnings)
+            .add_system(render_object_editor)
+            .add_system(render_dose_mesh_window)
+            .add_system(render_spectrometers)
+            .add_system(render_surface_detectors);
     }
 }
 
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 fn render_main_ui(
     mut contexts: EguiContexts,
     mut time_data: ResMut<TimeData>,
-    substance_data: Res<SubstanceData>,
+    mut substance_data: ResMut<SubstanceData>,
+    mut compound_editor_state: Local<CompoundEditorState>,
     mut interface_state: ResMut<InterfaceState>,
     env_state: ResMut<State<CurrentEnv>>,
     mut next_env_state: ResMut<NextState<CurrentEnv>>,
@@ -30,25 +41,69 @@ fn render_main_ui(
     mut reset_event: EventWriter<ResetParticles>,
 
     mut set: ParamSet<(
-        Query<(&Object, &Transform), With<Human>>,
+        Query<(
+            Entity,
+            &Object,
+            &GlobalTransform,
+            &GroupMember,
+            Option<&Name>,
+        )>,
         Query<(&mut Object, &mut Transform), With<ExperimentTarget>>,
+        Query<(Entity, &Object, &GlobalTransform, &Name)>,
     )>,
     mut experiment_spawner: Query<&mut LinearSpawner>,
+    mut dosimetry: ResMut<Dosimetry>,
+    group_query: Query<(Entity, &Name), With<ObjectGroup>>,
+    detector_query: Query<(Entity, &SurfaceDetector, &GlobalTransform), With<ExperimentTarget>>,
+    mut spectrum_type: Local<ParticleType>,
 ) {
     egui::Window::new("Simulation von Radioaktivität").anchor(egui::Align2::LEFT_TOP, [10.0, 10.0]).show(contexts.ctx_mut(), |ui| {
 
         if matches!(env_state.0, CurrentEnv::Sandbox) {
             ui.heading("Messwerte");
 
-            let equivalent_dose: f32 = set.p0().iter().map(|(object, transform)| {
-                // calculate equivalent dose for the current human body estimation
-                let volume = transform.scale.x * transform.scale.y * transform.scale.z;
-                let weight = object.material.average_density() * volume;
-                object.absorbed_energy * *EV_CONVERSION as f32 / weight
-            }).sum();
+            ui.horizontal(|ui| {
+                ui.label("Wichtungsfaktoren");
+                weighting_model_selector(ui, &mut dosimetry.model);
+            });
+
+            for (group, name) in group_query.iter() {
+                let member_query = set.p0();
+                let group_dose = GroupDose::new(
+                    member_query
+                        .iter()
+                        .filter(|(_, _, _, member, _)| member.0 == group)
+                        .map(|(entity, object, global_transform, _, _)| (entity, object, global_transform)),
+                    dosimetry.model,
+                );
+                let equivalent_dose = group_dose.equivalent_dose(dosimetry.model);
 
-            ui.label(format!("Äquivalenzdosis: {} mSv", equivalent_dose * 1_000.0));
-            ui.label(format!("Äquivalenzdosis/s: {} mSv/s", (equivalent_dose / time_data.time_passed) * 1_000.0));
+                ui.label(format!("Energiedosis ({}): {} mGy", name, group_dose.absorbed_dose() * 1_000.0));
+                ui.label(format!("Äquivalenzdosis ({}): {} mSv", name, equivalent_dose * 1_000.0));
+                ui.label(format!("Äquivalenzdosis/s ({}): {} mSv/s", name, (equivalent_dose / time_data.time_passed) * 1_000.0));
+                ui.collapsing(format!("Teile ({})", name), |ui| {
+                    part_dose_table(ui, (group, "part_doses"), &group_dose.parts, |entity| {
+                        member_query
+                            .get(entity)
+                            .ok()
+                            .and_then(|(_, _, _, _, name)| name)
+                            .map_or_else(String::new, |name| name.to_string())
+                    });
+                });
+            }
+
+            for (entity, object, global_transform, name) in set.p2().iter() {
+                let organ_doses = organ_doses(object, global_transform, dosimetry.model);
+                if organ_doses.is_empty() {
+                    continue;
+                }
+
+                ui.separator();
+                ui.label(format!("Effektive Dosis ({}): {} mSv", name, effective_dose(&organ_doses) * 1_000.0));
+                ui.collapsing(format!("Organdosen ({})", name), |ui| {
+                    organ_dose_table(ui, (entity, "organ_doses"), &organ_doses);
+                });
+            }
         } else if matches!(env_state.0, CurrentEnv::Experiment) {
             ui.heading("Test Objekt Material");
             let mut query = set.p1();
@@ -56,6 +111,10 @@ fn render_main_ui(
 
             material_editor(ui, &mut target.0.material, &substance_data, false);
 
+            ui.collapsing("Neue Verbindung", |ui| {
+                compound_editor(ui, &mut substance_data, &mut compound_editor_state);
+            });
+
             ui.horizontal(|ui| {
                 ui.label("Dicke");
                 ui.add(
@@ -114,23 +173,27 @@ fn render_main_ui(
                 });
                 spawner.particle_energy = (10.0f32).powf(energy_log);
             });
+
+            ui.collapsing("Durchgang", |ui| {
+                for (entity, detector, global_transform) in detector_query.iter() {
+                    detector_readout(ui, entity, detector, global_transform, time_data.time_passed, &mut spectrum_type);
+                }
+            });
         }
 
         ui.label(format!("Anzahl simulierte Teilchen: {}", particle_query.iter().len()));
 
         ui.separator();
 
-        time_editor(ui, &mut *time_data);
+        time_editor(ui, &mut time_data);
 
         ui.collapsing("Erweitert", |ui| {
             if !matches!(env_state.0, CurrentEnv::Sandbox) {
                 if ui.button("Sandbox").clicked() {
                     next_env_state.set(CurrentEnv::Sandbox);
                 }
-            } else if !matches!(env_state.0, CurrentEnv::Experiment) {
-                if  ui.button("Experiment").clicked() {
-                    next_env_state.set(CurrentEnv::Experiment);
-                }
+            } else if !matches!(env_state.0, CurrentEnv::Experiment) && ui.button("Experiment").clicked() {
+                next_env_state.set(CurrentEnv::Experiment);
             }
 
             if !interface_state.edit_objects {
@@ -141,6 +204,14 @@ fn render_main_ui(
                 interface_state.edit_objects = false;
             }
 
+            if !interface_state.dose_mesh {
+                if ui.button("Dosisverteilung").clicked() {
+                    interface_state.dose_mesh = true;
+                }
+            } else if ui.button("Dosisverteilung schließen").clicked() {
+                interface_state.dose_mesh = false;
+            }
+
             if ui.button("Zurücksetzen").clicked() {
                 reset_event.send_default();
             }
@@ -168,6 +239,100 @@ fn render_main_ui(
     });
 }
 
+/// Problems in the data files, only shown in lenient mode since strict mode fails at startup
+fn render_data_warnings(mut contexts: EguiContexts, substance_data: Res<SubstanceData>) {
+    if substance_data.warnings.is_empty() {
+        return;
+    }
+
+    egui::Window::new(format!(
+        "Datenwarnungen ({})",
+        substance_data.warnings.len()
+    ))
+    .id(egui::Id::new("data_warnings"))
+    .anchor(egui::Align2::RIGHT_BOTTOM, [-10.0, -10.0])
+    .default_open(false)
+    .show(contexts.ctx_mut(), |ui| {
+        egui::ScrollArea::vertical()
+            .max_height(300.0)
+            .show(ui, |ui| {
+                for warning in &substance_data.warnings {
+                    ui.label(warning.to_string());
+                }
+            });
+    });
+}
+
+/// Readouts of the sandbox detectors, planes are placed here, boundaries follow their object
+fn render_surface_detectors(
+    mut contexts: EguiContexts,
+    mut detector_query: Query<
+        (
+            Entity,
+            &SurfaceDetector,
+            &Name,
+            &mut Transform,
+            &GlobalTransform,
+        ),
+        Without<ExperimentTarget>,
+    >,
+    time_data: Res<TimeData>,
+    mut spectrum_type: Local<ParticleType>,
+
+    mut commands: Commands,
+) {
+    if detector_query.is_empty() {
+        return;
+    }
+
+    egui::Window::new("Durchgangsdetektoren")
+        .id(egui::Id::new("surface_detectors"))
+        .anchor(egui::Align2::CENTER_BOTTOM, [0.0, -10.0])
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            for (entity, detector, name, mut transform, global_transform) in
+                detector_query.iter_mut()
+            {
+                ui.collapsing(name.as_str(), |ui| {
+                    if let DetectorSurface::Plane = detector.surface {
+                        position_editor(ui, &mut transform);
+                        rotation_editor(ui, &mut transform);
+
+                        // the plane is the local y/z square, its normal the local x axis
+                        ui.label("Größe (m)");
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                egui::DragValue::new(&mut transform.scale.z)
+                                    .clamp_range(0..=100)
+                                    .speed(0.05),
+                            );
+                            ui.add(
+                                egui::DragValue::new(&mut transform.scale.y)
+                                    .clamp_range(0..=100)
+                                    .speed(0.05),
+                            );
+                        });
+                    }
+
+                    detector_readout(
+                        ui,
+                        entity,
+                        detector,
+                        global_transform,
+                        time_data.time_passed,
+                        &mut spectrum_type,
+                    );
+
+                    if let DetectorSurface::Plane = detector.surface {
+                        if ui.button("Entfernen").clicked() {
+                            commands.entity(entity).despawn_recursive();
+                        }
+                    }
+                });
+            }
+        });
+}
+
 fn render_legend(mut contexts: EguiContexts) {
     egui::Window::new("Legende")
         .anchor(egui::Align2::LEFT_BOTTOM, [10.0, -10.0])
@@ -199,16 +364,352 @@ fn render_legend(mut contexts: EguiContexts) {
         });
 }
 
+/// Which slice of a dose mesh is shown
+#[derive(Debug, Clone)]
+struct SliceView {
+    /// 0 = x, 1 = y, 2 = z
+    axis: usize,
+    index: u32,
+    logarithmic: bool,
+}
+
+impl Default for SliceView {
+    fn default() -> Self {
+        Self {
+            axis: 1,
+            index: 0,
+            logarithmic: true,
+        }
+    }
+}
+
+#[derive(Debug, Deref, DerefMut)]
+struct ExportPath(String);
+
+impl Default for ExportPath {
+    fn default() -> Self {
+        Self("dosisverteilung.csv".to_owned())
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn render_dose_mesh_window(
+    mut contexts: EguiContexts,
+    mut interface_state: ResMut<InterfaceState>,
+    mut mesh_query: Query<(
+        Entity,
+        &mut DoseMesh,
+        &mut Name,
+        &mut Transform,
+        &GlobalTransform,
+    )>,
+    env_state: Res<State<CurrentEnv>>,
+    mut views: Local<HashMap<Entity, SliceView>>,
+    mut export_path: Local<ExportPath>,
+    mut commands: Commands,
+) {
+    egui::Window::new("Dosisverteilung")
+        .open(&mut interface_state.dose_mesh)
+        .show(contexts.ctx_mut(), |ui| {
+            for (entity, mut mesh, mut name, mut transform, global_transform) in
+                mesh_query.iter_mut()
+            {
+                let view = views.entry(entity).or_default();
+
+                ui.collapsing(name.clone().as_str(), |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Name");
+                        name.mutate(|n| {
+                            ui.text_edit_singleline(n);
+                        })
+                    });
+
+                    position_editor(ui, &mut transform);
+
+                    ui.label("Größe (m) (x, y, z)");
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::DragValue::new(&mut transform.scale.x)
+                                .clamp_range(0.01..=100.0)
+                                .speed(0.05),
+                        );
+                        // y/z flipped to match with mathematicl norm
+                        ui.add(
+                            egui::DragValue::new(&mut transform.scale.z)
+                                .clamp_range(0.01..=100.0)
+                                .speed(0.05),
+                        );
+                        ui.add(
+                            egui::DragValue::new(&mut transform.scale.y)
+                                .clamp_range(0.01..=100.0)
+                                .speed(0.05),
+                        );
+                    });
+
+                    // changing the voxel counts starts a new tally
+                    let mut dimensions = mesh.dimensions();
+                    ui.label("Voxel (x, y, z)");
+                    ui.horizontal(|ui| {
+                        ui.add(egui::DragValue::new(&mut dimensions.x).clamp_range(1..=200));
+                        ui.add(egui::DragValue::new(&mut dimensions.z).clamp_range(1..=200));
+                        ui.add(egui::DragValue::new(&mut dimensions.y).clamp_range(1..=200));
+                    });
+                    if dimensions != mesh.dimensions() {
+                        *mesh = DoseMesh::new(dimensions);
+                    }
+
+                    ui.separator();
+
+                    ui.horizontal(|ui| {
+                        ui.label("Schnitt");
+                        // y/z flipped to match with mathematicl norm
+                        for (axis, label) in [(0, "x"), (2, "y"), (1, "z")] {
+                            ui.radio_value(&mut view.axis, axis, label);
+                        }
+                    });
+                    let slice_count = mesh.dimensions()[view.axis];
+                    view.index = view.index.min(slice_count - 1);
+                    ui.add(egui::Slider::new(&mut view.index, 0..=slice_count - 1).text("Ebene"));
+                    ui.checkbox(&mut view.logarithmic, "Logarithmisch");
+
+                    let slice = mesh.slice(view.axis, view.index, global_transform);
+                    ui.label(format!("Maximum: {:.3e} mGy", slice.max() * 1_000.0));
+                    dose_heat_map(ui, &slice, view.logarithmic);
+
+                    ui.separator();
+
+                    ui.horizontal(|ui| {
+                        if ui.button("CSV kopieren").clicked() {
+                            let csv = mesh.to_csv(global_transform);
+                            ui.output_mut(|output| output.copied_text = csv);
+                        }
+                        #[cfg(not(target_arch = "wasm32"))]
+                        {
+                            ui.text_edit_singleline(&mut export_path.0);
+                            if ui.button("Speichern").clicked() {
+                                if let Err(e) = std::fs::write(
+                                    export_path.as_str(),
+                                    mesh.to_csv(global_transform),
+                                ) {
+                                    log::error!("Could not export dose mesh: {}", e);
+                                }
+                            }
+                        }
+                    });
+
+                    if ui.button("Entfernen").clicked() {
+                        commands.entity(entity).despawn_recursive();
+                    }
+                });
+            }
+
+            if ui.button("Neue Dosisverteilung").clicked() {
+                let mut entity_commands = commands.spawn((
+                    Name::new("Dosisverteilung"),
+                    SpatialBundle::from_transform(
+                        Transform::from_xyz(0.0, 0.5, 0.0).with_scale(Vec3::ONE),
+                    ),
+                    DoseMesh::new(UVec3::splat(20)),
+                ));
+                match env_state.0 {
+                    CurrentEnv::Sandbox => entity_commands.insert(SandboxObject),
+                    CurrentEnv::Experiment => entity_commands.insert(ExperimentObject),
+                };
+            }
+        });
+}
+
+/// Pulse-height spectra of all detectors, only shown if there are any
+fn render_spectrometers(
+    mut contexts: EguiContexts,
+    mut detector_query: Query<(Entity, &mut PulseHeightDetector, &Name)>,
+    mut logarithmic: Local<bool>,
+) {
+    if detector_query.is_empty() {
+        return;
+    }
+
+    egui::Window::new("Spektrometer")
+        .id(egui::Id::new("spectrometers"))
+        .anchor(egui::Align2::LEFT_BOTTOM, [10.0, -10.0])
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.checkbox(&mut logarithmic, "Logarithmisch");
+
+            for (entity, mut detector, name) in detector_query.iter_mut() {
+                ui.collapsing(name.as_str(), |ui| {
+                    ui.label(format!(
+                        "Ereignisse: {}",
+                        detector.spectrum.iter().sum::<u64>()
+                    ));
+
+                    let mut broadened = detector.resolution.is_some();
+                    let mut resolution = detector.resolution.unwrap_or(0.07) * 100.0;
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut broadened, "Auflösung (FWHM bei 662 keV)");
+                        ui.add_enabled(
+                            broadened,
+                            egui::DragValue::new(&mut resolution)
+                                .clamp_range(0.01..=50.0)
+                                .speed(0.01)
+                                .suffix(" %"),
+                        );
+                    });
+                    let new_resolution = broadened.then_some(resolution / 100.0);
+                    if new_resolution != detector.resolution {
+                        detector.resolution = new_resolution;
+                    }
+
+                    // a new range or channel count starts a new spectrum
+                    let mut max_energy = detector.max_energy / 1_000.0;
+                    let mut channels = detector.spectrum.len();
+                    ui.horizontal(|ui| {
+                        ui.label("Bereich (keV)");
+                        ui.add(
+                            egui::DragValue::new(&mut max_energy)
+                                .clamp_range(1.0..=20_000.0)
+                                .speed(10.0),
+                        );
+                        ui.label("Kanäle");
+                        ui.add(egui::DragValue::new(&mut channels).clamp_range(16..=16_384));
+                    });
+                    if max_energy * 1_000.0 != detector.max_energy
+                        || channels != detector.spectrum.len()
+                    {
+                        detector.max_energy = max_energy * 1_000.0;
+                        detector.set_channels(channels);
+                    }
+
+                    let channel_width = detector.channel_width() / 1_000.0;
+                    let points = detector
+                        .spectrum
+                        .iter()
+                        .enumerate()
+                        .map(|(channel, count)| {
+                            let count = if *logarithmic {
+                                (*count as f64 + 1.0).log10()
+                            } else {
+                                *count as f64
+                            };
+                            [(channel as f64 + 0.5) * channel_width as f64, count]
+                        })
+                        .collect::<egui::plot::PlotPoints>();
+                    ui.label(if *logarithmic {
+                        "log10(Ereignisse + 1) über Energie (keV)"
+                    } else {
+                        "Ereignisse über Energie (keV)"
+                    });
+                    egui::plot::Plot::new((entity, "pulse_height_spectrum"))
+                        .height(200.0)
+                        .show(ui, |plot_ui| plot_ui.line(egui::plot::Line::new(points)));
+
+                    if ui.button("Spektrum löschen").clicked() {
+                        detector.clear();
+                    }
+                });
+            }
+        });
+}
+
+/// Color of a value from 0 to 1, black over red and yellow to white
+fn heat_color(value: f32) -> egui::Color32 {
+    let value = value.clamp(0.0, 1.0) * 3.0;
+    egui::Color32::from_rgb(
+        (value.min(1.0) * 255.0) as u8,
+        ((value - 1.0).clamp(0.0, 1.0) * 255.0) as u8,
+        ((value - 2.0).clamp(0.0, 1.0) * 255.0) as u8,
+    )
+}
+
+/// Slice of a dose mesh as colored cells, logarithmic over four decades below the maximum
+fn dose_heat_map(ui: &mut egui::Ui, slice: &Slice, logarithmic: bool) {
+    const MAX_SIZE: f32 = 300.0;
+    const DECADES: f32 = 4.0;
+
+    let cell_size = MAX_SIZE / slice.columns.max(slice.rows) as f32;
+    let (response, painter) = ui.allocate_painter(
+        egui::vec2(
+            cell_size * slice.columns as f32,
+            cell_size * slice.rows as f32,
+        ),
+        egui::Sense::hover(),
+    );
+    let max = slice.max();
+    let origin = response.rect.left_top();
+
+    for row in 0..slice.rows {
+        for column in 0..slice.columns {
+            let dose = slice.dose(column, row);
+            let value = if max <= 0.0 || dose <= 0.0 {
+                0.0
+            } else if logarithmic {
+                1.0 + (dose / max).log10() / DECADES
+            } else {
+                dose / max
+            };
+            // first row at the bottom
+            let min = origin
+                + egui::vec2(
+                    column as f32 * cell_size,
+                    (slice.rows - 1 - row) as f32 * cell_size,
+                );
+            painter.rect_filled(
+                egui::Rect::from_min_size(min, egui::vec2(cell_size, cell_size)),
+                0.0,
+                heat_color(value),
+            );
+        }
+    }
+
+    if let Some(position) = response.hover_pos() {
+        let relative = (position - origin) / cell_size;
+        let column = (relative.x as u32).min(slice.columns - 1);
+        let row = slice.rows - 1 - (relative.y as u32).min(slice.rows - 1);
+        response.on_hover_text(format!(
+            "{}, {}: {:.3e} mGy",
+            column,
+            row,
+            slice.dose(column, row) * 1_000.0
+        ));
+    }
+}
+
+#[derive(Debug, Deref, DerefMut)]
+struct PhantomPath(String);
+
+impl Default for PhantomPath {
+    fn default() -> Self {
+        Self("phantoms/water_cylinder.phantom".to_owned())
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 fn render_object_editor(
     mut contexts: EguiContexts,
     mut interface_state: ResMut<InterfaceState>,
     mut set: ParamSet<(
-        Query<(Entity, &mut Object, &mut Name, &mut Transform), Without<Human>>,
+        Query<
+            (
+                Entity,
+                &mut Object,
+                &mut Name,
+                &mut Transform,
+                &GlobalTransform,
+                Option<&SurfaceDetector>,
+                Option<&PulseHeightDetector>,
+            ),
+            Without<Human>,
+        >,
         Query<&mut Transform, With<HumanRoot>>,
         Query<&mut AmbientMaterial>,
     )>,
     asset_handles: Res<AssetHandles>,
-    substance_data: Res<SubstanceData>,
+    asset_server: Res<AssetServer>,
+    mut substance_data: ResMut<SubstanceData>,
+    mut phantom_path: Local<PhantomPath>,
+    mut compound_editor_state: Local<CompoundEditorState>,
+    time_data: Res<TimeData>,
+    dosimetry: Res<Dosimetry>,
 
     mut commands: Commands,
 ) {
@@ -217,7 +718,16 @@ fn render_object_editor(
         .open(&mut interface_state.edit_objects)
         .show(contexts.ctx_mut(), |ui| {
             let mut i = 1;
-            for (entity, mut object, mut name, mut transform) in set.p0().iter_mut() {
+            for (
+                entity,
+                mut object,
+                mut name,
+                mut transform,
+                global_transform,
+                surface_detector,
+                pulse_height_detector,
+            ) in set.p0().iter_mut()
+            {
                 ui.collapsing(name.clone().as_str(), |ui| {
                     ui.horizontal(|ui| {
                         ui.label("Name");
@@ -252,10 +762,90 @@ fn render_object_editor(
                         material_editor(ui, &mut object.material, &substance_data, true);
                     });
 
-                    ui.label(format!("Absorbierte Energie: {}eV", object.absorbed_energy));
+                    ui.collapsing("Aktivität", |ui| {
+                        activity_editor(
+                            ui,
+                            entity,
+                            &mut object,
+                            global_transform,
+                            time_data.start_date,
+                            time_data.time_passed,
+                        );
+                    });
+
+                    ui.label(format!(
+                        "Absorbierte Energie: {}eV",
+                        object.absorbed_energy.total()
+                    ));
+
+                    ui.collapsing("Energiedeposition", |ui| {
+                        energy_deposit_table(
+                            ui,
+                            entity,
+                            &object.absorbed_energy,
+                            object.mass(global_transform),
+                            dosimetry.model,
+                        );
+                    });
+
+                    if !object.geometry.regions().is_empty() {
+                        ui.collapsing("Regionen", |ui| {
+                            for (i, (region, energy)) in object
+                                .geometry
+                                .regions()
+                                .iter()
+                                .zip(&object.region_absorbed_energy)
+                                .enumerate()
+                            {
+                                let mass = object.region_mass(i, global_transform);
+                                if mass > 0.0 {
+                                    ui.label(format!(
+                                        "{} ({}): {}eV, {} mGy, {} mSv",
+                                        region.name,
+                                        region.id,
+                                        energy.total(),
+                                        energy.absorbed_dose(mass) * 1_000.0,
+                                        energy.equivalent_dose(mass, dosimetry.model) * 1_000.0
+                                    ));
+                                }
+                            }
+                        });
+                    }
+
+                    // the readout is in the detector window
+                    let mut has_surface_detector = surface_detector.is_some();
+                    if ui
+                        .checkbox(&mut has_surface_detector, "Durchgangsdetektor")
+                        .changed()
+                    {
+                        if has_surface_detector {
+                            commands.entity(entity).insert(SurfaceDetector::new(
+                                DetectorSurface::Boundary(object.geometry.clone()),
+                            ));
+                        } else {
+                            commands.entity(entity).remove::<SurfaceDetector>();
+                        }
+                    }
+
+                    // an ideal detector up to 2 MeV, the spectrometer window changes it
+                    let mut has_pulse_height_detector = pulse_height_detector.is_some();
+                    if ui
+                        .checkbox(&mut has_pulse_height_detector, "Spektrometer")
+                        .changed()
+                    {
+                        if has_pulse_height_detector {
+                            commands.entity(entity).insert(PulseHeightDetector::new(
+                                1024,
+                                2_000_000.0,
+                                None,
+                            ));
+                        } else {
+                            commands.entity(entity).remove::<PulseHeightDetector>();
+                        }
+                    }
 
                     if ui.button("Entfernen").clicked() {
-                        commands.entity(entity).despawn();
+                        commands.entity(entity).despawn_recursive();
                     }
                 });
 
@@ -280,6 +870,34 @@ fn render_object_editor(
                 ));
             }
 
+            if ui.button("Neuer Ebenen-Detektor").clicked() {
+                commands.spawn((
+                    Name::new(format!("Ebene {}", i)),
+                    PbrBundle {
+                        material: asset_handles.grey_material.as_ref().unwrap().clone(),
+                        mesh: asset_handles.cube_mesh.as_ref().unwrap().clone(),
+                        // thin along the normal, only the local y/z square detects
+                        transform: Transform::from_xyz(0.0, 1.0, 0.0)
+                            .with_scale(Vec3::new(0.005, 1.0, 1.0)),
+                        ..Default::default()
+                    },
+                    SurfaceDetector::new(DetectorSurface::Plane),
+                    SandboxObject,
+                ));
+            }
+
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut phantom_path.0);
+                if ui.button("Phantom laden").clicked() {
+                    commands.spawn((
+                        Name::new(format!("Phantom {}", i)),
+                        SpatialBundle::default(),
+                        VoxelPhantomSource(asset_server.load(phantom_path.as_str())),
+                        SandboxObject,
+                    ));
+                }
+            });
+
             ui.collapsing("Mensch", |ui| {
                 let mut human_query = set.p1();
                 let mut transform = human_query.iter_mut().next().unwrap();
@@ -293,6 +911,10 @@ fn render_object_editor(
 
                 material_editor(ui, material, &substance_data, false);
             });
+
+            ui.collapsing("Neue Verbindung", |ui| {
+                compound_editor(ui, &mut substance_data, &mut compound_editor_state);
+            });
         });
 }
 
@@ -320,6 +942,11 @@ fn time_editor(ui: &mut egui::Ui, time_data: &mut TimeData) {
         });
         time_data.time_step_move = (10.0f32).powf(-time_step_move_log);
 
+        ui.horizontal(|ui| {
+            ui.label("Simulationsdatum:");
+            date_editor(ui, &mut time_data.start_date);
+        });
+
         ui.horizontal(|ui| {
             ui.label("Multischritt:");
             ui.add(
@@ -339,6 +966,93 @@ fn time_editor(ui: &mut egui::Ui, time_data: &mut TimeData) {
     });
 }
 
+/// Day, month and year
+fn date_editor(ui: &mut egui::Ui, date: &mut Date) {
+    let days_in_month = date.days_in_month();
+    ui.add(egui::DragValue::new(&mut date.day).clamp_range(1..=days_in_month));
+    ui.label(".");
+    ui.add(egui::DragValue::new(&mut date.month).clamp_range(1..=12));
+    ui.label(".");
+    ui.add(egui::DragValue::new(&mut date.year).clamp_range(1900..=2200));
+    // a shorter month or a year without leap day
+    date.day = date.day.min(date.days_in_month());
+}
+
+fn activity_editor(
+    ui: &mut egui::Ui,
+    entity: Entity,
+    object: &mut Object,
+    global_transform: &GlobalTransform,
+    start_date: Date,
+    time_passed: f32,
+) {
+    let mut fixed_activity = object.source_activity.is_some();
+    ui.checkbox(&mut fixed_activity, "Aktivität festlegen");
+    if fixed_activity && object.source_activity.is_none() {
+        // start with the activity the material had at the start of the simulation,
+        // so the current activity doesn't jump
+        object.source_activity = Some(SourceActivity {
+            activity: object
+                .activities(global_transform, start_date, 0.0)
+                .iter()
+                .map(|(_, activity)| activity)
+                .sum(),
+            reference_date: start_date,
+            unit: ActivityUnit::Becquerel,
+        });
+    } else if !fixed_activity {
+        object.source_activity = None;
+    }
+
+    if let Some(source_activity) = &mut object.source_activity {
+        ui.horizontal(|ui| {
+            ui.label("Am Referenzdatum:");
+            let unit = source_activity.unit;
+            let mut activity = source_activity.activity / unit.value();
+            let speed = activity.max(1.0) * 0.01;
+            ui.add(
+                egui::DragValue::new(&mut activity)
+                    .clamp_range(0.0..=f32::MAX)
+                    .speed(speed),
+            );
+            source_activity.activity = activity * unit.value();
+
+            egui::ComboBox::from_id_source(("activity_unit", entity))
+                .selected_text(unit.symbol())
+                .show_ui(ui, |ui| {
+                    for unit in [ActivityUnit::Becquerel, ActivityUnit::Curie] {
+                        ui.selectable_value(&mut source_activity.unit, unit, unit.symbol());
+                    }
+                });
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Referenzdatum:");
+            date_editor(ui, &mut source_activity.reference_date);
+        });
+        ui.label(format!(
+            "{} Tage bis zum Simulationsdatum",
+            source_activity.reference_date.seconds_until(start_date) / 86_400.0
+        ));
+    }
+
+    let activity: f32 = object
+        .activities(global_transform, start_date, time_passed)
+        .iter()
+        .map(|(_, activity)| activity)
+        .sum();
+    let unit = object
+        .source_activity
+        .map_or(ActivityUnit::Becquerel, |source_activity| {
+            source_activity.unit
+        });
+    ui.label(format!(
+        "Aktuelle Aktivität: {:.3e} {}",
+        activity / unit.value(),
+        unit.symbol()
+    ));
+}
+
 fn position_editor(ui: &mut egui::Ui, transform: &mut Transform) {
     ui.label("Position (m) (x, y, z)");
     ui.horizontal(|ui| {
@@ -361,23 +1075,70 @@ fn position_editor(ui: &mut egui::Ui, transform: &mut Transform) {
     });
 }
 
+fn rotation_editor(ui: &mut egui::Ui, transform: &mut Transform) {
+    let (x, y, z) = transform.rotation.to_euler(EulerRot::XYZ);
+    let mut angles = Vec3::new(x.to_degrees(), y.to_degrees(), z.to_degrees());
+    let previous_angles = angles;
+
+    ui.label("Drehung (°) (x, y, z)");
+    ui.horizontal(|ui| {
+        ui.add(
+            egui::DragValue::new(&mut angles.x)
+                .clamp_range(-180..=180)
+                .speed(1.0),
+        );
+        // y/z flipped to match with mathematicl norm
+        ui.add(
+            egui::DragValue::new(&mut angles.z)
+                .clamp_range(-180..=180)
+                .speed(1.0),
+        );
+        ui.add(
+            egui::DragValue::new(&mut angles.y)
+                .clamp_range(-180..=180)
+                .speed(1.0),
+        );
+    });
+
+    // converting back and forth every frame would let the rotation drift
+    if angles != previous_angles {
+        transform.rotation = Quat::from_euler(
+            EulerRot::XYZ,
+            angles.x.to_radians(),
+            angles.y.to_radians(),
+            angles.z.to_radians(),
+        );
+    }
+}
+
+/// Substances with calculated data are marked with "≈"
+fn substance_label(substance: &Substance) -> String {
+    if substance.approximated().is_empty() {
+        format!("{}", substance)
+    } else {
+        format!("{} ≈", substance)
+    }
+}
+
 fn material_editor(
     ui: &mut egui::Ui,
     material: &mut MaterialData,
-    substance_data: &Res<SubstanceData>,
+    substance_data: &SubstanceData,
     show_radiators: bool,
 ) {
-    let len = material.parts.len();
+    // edited as a copy, setting the parts updates the material
+    let mut parts = material.parts().to_vec();
+    let len = parts.len();
     let mut to_remove = None;
-    for (i, (ratio, substance)) in &mut material.parts.iter_mut().enumerate() {
+    for (i, (ratio, substance)) in parts.iter_mut().enumerate() {
         egui::ComboBox::from_label(format!("Material Typ {}", i))
-            .selected_text(format!("{}", substance))
+            .selected_text(substance_label(substance))
             .show_ui(ui, |ui| {
                 for new_substance in &substance_data.absorbers {
                     ui.selectable_value(
                         substance,
                         new_substance.to_owned(),
-                        format!("{}", new_substance),
+                        substance_label(new_substance),
                     );
                 }
                 if show_radiators {
@@ -385,12 +1146,19 @@ fn material_editor(
                         ui.selectable_value(
                             substance,
                             new_substance.to_owned(),
-                            format!("{}", new_substance),
+                            substance_label(new_substance),
                         );
                     }
                 }
             });
 
+        if !substance.approximated().is_empty() {
+            ui.label("≈ Näherungswerte").on_hover_text(format!(
+                "Berechnete statt gemessene Werte für: {:?}",
+                substance.approximated()
+            ));
+        }
+
         ui.horizontal(|ui| {
             ui.label("Anteil:");
             ui.add(
@@ -407,20 +1175,375 @@ fn material_editor(
         ui.label("");
     }
     if let Some(i) = to_remove {
-        material.parts.remove(i);
+        parts.remove(i);
     }
 
     if ui.button("Neu").clicked() {
-        material
-            .parts
-            .push((0.5, substance_data.absorbers[0].clone()));
+        parts.push((0.5, substance_data.absorbers[0].clone()));
+    }
+
+    let mut override_density = material.density_override().is_some();
+    ui.checkbox(&mut override_density, "Dichte überschreiben");
+    if override_density {
+        // edit in g/cm3, stored in kg/m3
+        let mut density = material
+            .density_override()
+            .unwrap_or(material.average_density())
+            / 1000.0;
+        ui.horizontal(|ui| {
+            ui.label("Dichte (g/cm³)");
+            ui.add(
+                egui::DragValue::new(&mut density)
+                    .clamp_range(0.0..=25.0)
+                    .speed(0.001),
+            );
+        });
+        material.set_density_override(Some(density * 1000.0));
+    } else {
+        material.set_density_override(None);
     }
 
     // normalize ratios
-    let total_ratios: f32 = material.parts.iter().map(|m| m.0).sum();
-    for (ratio, _) in &mut material.parts {
+    let total_ratios: f32 = parts.iter().map(|m| m.0).sum();
+    for (ratio, _) in &mut parts {
         *ratio /= total_ratios;
     }
+
+    if parts != material.parts() {
+        material.set_parts(parts);
+    }
+
+    if !material.incomplete_stopping_powers().is_empty() {
+        ui.label("≈ Unvollständiges Bremsvermögen")
+            .on_hover_text(format!(
+                "Nicht alle Anteile haben Daten für: {:?}",
+                material.incomplete_stopping_powers()
+            ));
+    }
+}
+
+fn particle_name(particle_type: ParticleType) -> &'static str {
+    match particle_type {
+        ParticleType::Alpha => "α-Teilchen",
+        ParticleType::Electron => "Elektron",
+        ParticleType::Proton => "Proton",
+        ParticleType::Neutron => "Neutron",
+        ParticleType::Gamma => "Photon",
+    }
+}
+
+/// Deposited energy and dose of each particle type, `mass` in kg
+fn energy_deposit_table(
+    ui: &mut egui::Ui,
+    id_source: impl std::hash::Hash,
+    deposits: &EnergyDeposits,
+    mass: f32,
+    model: WeightingModel,
+) {
+    egui::Grid::new(id_source).striped(true).show(ui, |ui| {
+        ui.label("Strahlung");
+        ui.label("Primär");
+        ui.label("Sekundär");
+        ui.label("Energiedosis");
+        ui.label("Äquivalenzdosis");
+        ui.end_row();
+
+        for particle_type in PARTICLE_TYPES {
+            if deposits.by_type(particle_type) <= 0.0 {
+                continue;
+            }
+            ui.label(particle_name(particle_type));
+            for generation in [Generation::Primary, Generation::Secondary] {
+                ui.label(format!(
+                    "{:.3e} eV",
+                    deposits.by_type_and_generation(particle_type, generation)
+                ));
+            }
+            if mass > 0.0 {
+                ui.label(format!(
+                    "{:.3e} mGy",
+                    deposits.absorbed_dose_by_type(particle_type, mass) * 1_000.0
+                ));
+                ui.label(format!(
+                    "{:.3e} mSv",
+                    deposits.equivalent_dose_by_type(particle_type, mass, model) * 1_000.0
+                ));
+            }
+            ui.end_row();
+        }
+    });
+}
+
+/// Crossings listed in a readout, newest first
+const RECENT_CROSSINGS: usize = 20;
+
+/// Crossings, currents and fluences per particle type and the spectra of one type
+fn detector_readout(
+    ui: &mut egui::Ui,
+    entity: Entity,
+    detector: &SurfaceDetector,
+    global_transform: &GlobalTransform,
+    time_passed: f32,
+    spectrum_type: &mut ParticleType,
+) {
+    egui::Grid::new((entity, "detector_readout"))
+        .striped(true)
+        .show(ui, |ui| {
+            ui.label("Strahlung");
+            ui.label("Hinein");
+            ui.label("Hinaus");
+            ui.label("Netto-Strom");
+            ui.label("Fluenz");
+            ui.end_row();
+
+            for particle_type in PARTICLE_TYPES {
+                let Some(tally) = detector.tally(particle_type) else {
+                    continue;
+                };
+                ui.label(particle_name(particle_type));
+                ui.label(format!("{}", tally.inward));
+                ui.label(format!("{}", tally.outward));
+                ui.label(format!(
+                    "{:.3e} 1/s",
+                    detector.net_current(particle_type) as f32 / time_passed
+                ));
+                ui.label(format!(
+                    "{:.3e} 1/m²",
+                    detector.fluence(particle_type, global_transform)
+                ));
+                ui.end_row();
+            }
+        });
+
+    ui.collapsing(
+        format!("Letzte Durchgänge ({})", detector.crossings.len()),
+        |ui| {
+            egui::Grid::new((entity, "recent_crossings"))
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("Strahlung");
+                    ui.label("Energie");
+                    ui.label("Richtung (x, y, z)");
+                    ui.label("Zeit");
+                    ui.label("Seite");
+                    ui.end_row();
+
+                    for crossing in detector.crossings.iter().rev().take(RECENT_CROSSINGS) {
+                        ui.label(particle_name(crossing.particle_type));
+                        ui.label(format!("{:.3e} eV", crossing.energy));
+                        // y/z flipped to match with mathematicl norm
+                        ui.label(format!(
+                            "{:.2}, {:.2}, {:.2}",
+                            crossing.direction.x, crossing.direction.z, crossing.direction.y
+                        ));
+                        ui.label(format!("{:.3e} s", crossing.time));
+                        ui.label(if crossing.inward { "Hinein" } else { "Hinaus" });
+                        ui.end_row();
+                    }
+                });
+        },
+    );
+
+    ui.horizontal(|ui| {
+        egui::ComboBox::from_id_source((entity, "spectrum_type"))
+            .selected_text(particle_name(*spectrum_type))
+            .show_ui(ui, |ui| {
+                for particle_type in PARTICLE_TYPES {
+                    ui.selectable_value(spectrum_type, particle_type, particle_name(particle_type));
+                }
+            });
+        ui.label("Spektrum");
+    });
+
+    let Some(tally) = detector.tally(*spectrum_type) else {
+        ui.label("Keine Durchgänge");
+        return;
+    };
+
+    let bin_width = 1.0 / SPECTRUM_BINS_PER_DECADE as f64;
+    let energy_bars = tally
+        .energy_spectrum
+        .iter()
+        .enumerate()
+        .map(|(bin, count)| {
+            let log_energy = (spectrum_bin_energy(bin) as f64).log10();
+            egui::plot::Bar::new(log_energy + bin_width / 2.0, *count as f64).width(bin_width)
+        })
+        .collect();
+    ui.label("Energie: log10(E / eV)");
+    egui::plot::Plot::new((entity, "energy_spectrum"))
+        .height(150.0)
+        .show(ui, |plot_ui| {
+            plot_ui.bar_chart(egui::plot::BarChart::new(energy_bars))
+        });
+
+    let angle_width = 2.0 / ANGLE_BINS as f64;
+    let angle_bars = tally
+        .angle_spectrum
+        .iter()
+        .enumerate()
+        .map(|(bin, count)| {
+            egui::plot::Bar::new(-1.0 + (bin as f64 + 0.5) * angle_width, *count as f64)
+                .width(angle_width)
+        })
+        .collect();
+    ui.label("Richtung: cos zur x-Achse");
+    egui::plot::Plot::new((entity, "angle_spectrum"))
+        .height(150.0)
+        .show(ui, |plot_ui| {
+            plot_ui.bar_chart(egui::plot::BarChart::new(angle_bars))
+        });
+}
+
+fn part_dose_table(
+    ui: &mut egui::Ui,
+    id_source: impl std::hash::Hash,
+    parts: &[PartDose],
+    part_name: impl Fn(Entity) -> String,
+) {
+    egui::Grid::new(id_source).striped(true).show(ui, |ui| {
+        ui.label("Teil");
+        ui.label("Masse");
+        ui.label("Energiedosis");
+        ui.label("Äquivalenzdosis");
+        ui.end_row();
+
+        for part in parts {
+            ui.label(part_name(part.entity));
+            ui.label(format!("{:.3} kg", part.mass));
+            ui.label(format!("{:.3e} mGy", part.absorbed_dose * 1_000.0));
+            ui.label(format!("{:.3e} mSv", part.equivalent_dose * 1_000.0));
+            ui.end_row();
+        }
+    });
+}
+
+fn organ_dose_table(ui: &mut egui::Ui, id_source: impl std::hash::Hash, organ_doses: &[OrganDose]) {
+    egui::Grid::new(id_source).striped(true).show(ui, |ui| {
+        ui.label("Organ");
+        ui.label("Wichtung");
+        ui.label("Energiedosis");
+        ui.label("Äquivalenzdosis");
+        ui.end_row();
+
+        for organ in organ_doses {
+            ui.label(organ.tissue.name());
+            ui.label(format!("{}", organ.tissue.weighting_factor()));
+            ui.label(format!("{:.3e} mGy", organ.absorbed_dose * 1_000.0));
+            ui.label(format!("{:.3e} mSv", organ.equivalent_dose * 1_000.0));
+            ui.end_row();
+        }
+    });
+}
+
+fn weighting_model_selector(ui: &mut egui::Ui, model: &mut WeightingModel) {
+    egui::ComboBox::from_id_source("weighting_model")
+        .selected_text(model.name())
+        .show_ui(ui, |ui| {
+            for option in WeightingModel::ALL {
+                ui.selectable_value(model, option, option.name());
+            }
+        });
+}
+
+#[derive(Debug)]
+struct CompoundEditorState {
+    name: String,
+    /// formula or list of `symbol:mass fraction`
+    composition: String,
+    by_formula: bool,
+    /// in g/cm3
+    density: f32,
+    message: Option<String>,
+}
+
+impl Default for CompoundEditorState {
+    fn default() -> Self {
+        Self {
+            name: "PMMA".to_owned(),
+            composition: "C5H8O2".to_owned(),
+            by_formula: true,
+            density: 1.19,
+            message: None,
+        }
+    }
+}
+
+fn compound_editor(
+    ui: &mut egui::Ui,
+    substance_data: &mut SubstanceData,
+    state: &mut CompoundEditorState,
+) {
+    ui.horizontal(|ui| {
+        ui.radio_value(&mut state.by_formula, true, "Summenformel");
+        ui.radio_value(&mut state.by_formula, false, "Massenanteile");
+    });
+
+    ui.horizontal(|ui| {
+        ui.label("Name");
+        ui.text_edit_singleline(&mut state.name);
+    });
+
+    ui.horizontal(|ui| {
+        if state.by_formula {
+            ui.label("Formel");
+            ui.text_edit_singleline(&mut state.composition)
+                .on_hover_text("Isotope in eckigen Klammern, z.B. H[3H]O");
+        } else {
+            ui.label("Anteile");
+            ui.text_edit_singleline(&mut state.composition)
+                .on_hover_text("z.B. H:0.112, O:0.888 oder [3H]:0.01");
+        }
+    });
+
+    ui.horizontal(|ui| {
+        ui.label("Dichte (g/cm³)");
+        ui.add(
+            egui::DragValue::new(&mut state.density)
+                .clamp_range(0.0..=25.0)
+                .speed(0.01),
+        );
+    });
+
+    if ui.button("Verbindung erstellen").clicked() {
+        // convert from g/cm3 to kg/m3
+        let density = state.density * 1000.0;
+        let result = if state.by_formula {
+            substance_data.add_compound_from_formula(&state.name, &state.composition, density)
+        } else {
+            state
+                .composition
+                .split(',')
+                .map(|part| {
+                    let (nuclide, fraction) = part
+                        .split_once(':')
+                        .ok_or_else(|| CompoundError::InvalidFormula(part.to_owned()))?;
+                    let (z, mass_number) = parse_nuclide(nuclide, &substance_data.elements)?;
+                    let fraction = fraction
+                        .trim()
+                        .parse::<f32>()
+                        .map_err(|_| CompoundError::InvalidFormula(part.to_owned()))?;
+                    Ok((z, mass_number, fraction))
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .and_then(|mass_fractions| {
+                    substance_data.add_compound_from_mass_fractions(
+                        &state.name,
+                        &mass_fractions,
+                        density,
+                    )
+                })
+        };
+
+        state.message = Some(match result {
+            Ok(compound) => format!("{} erstellt", compound.name),
+            Err(e) => format!("Fehler: {}", e),
+        });
+    }
+
+    if let Some(message) = &state.message {
+        ui.label(message);
+    }
 }
 
 // egui::Window::new("Elemente").show(egui_context.ctx_mut(), |ui| {